@@ -0,0 +1,78 @@
+/*!
+This module defines `RegistryAudit`, a structured summary of the contents of a
+`Registry`, which is useful for startup self-checks and debugging.
+*/
+use crate::prelude::*;
+use crate::Registry;
+
+/// A summary of the contents of a registry. All of the fields are owned or
+/// `&'static`, so an audit can be stored or serialized freely.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistryAudit {
+    /// The number of traits that have a table in the registry.
+    pub trait_count: usize,
+    /// The total number of impls registered across all traits.
+    pub impl_count: usize,
+    /// The registered impls of each trait, sorted by trait name.
+    pub traits: Vec<TraitAudit>,
+    /// Pairs of `(trait name, impl name)` where more than one concrete type
+    /// with the same name is registered for the same trait. Names come from
+    /// `stringify!`, so this usually means two types with the same name in
+    /// different modules, which makes the names ambiguous in diagnostics.
+    pub duplicates: Vec<(&'static str, &'static str)>,
+    /// The names of traits whose tables have no impls at all.
+    pub empty_tables: Vec<&'static str>,
+}
+
+/// The audit of a single trait table in a registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraitAudit {
+    /// The name of the trait object type.
+    pub name: &'static str,
+    /// The names of the concrete types registered for the trait, sorted.
+    pub impls: Vec<&'static str>,
+}
+
+impl Registry {
+    /// Produces a summary of every table in the registry.
+    pub fn audit(&self) -> RegistryAudit {
+        let mut traits: Vec<TraitAudit> = self
+            .trait_metas()
+            .map(|meta| {
+                let mut impls: Vec<&'static str> = (meta.impls)(self)
+                    .into_iter()
+                    .map(|(_, name)| name)
+                    .collect();
+                impls.sort_unstable();
+                TraitAudit {
+                    name: meta.name,
+                    impls,
+                }
+            })
+            .collect();
+        traits.sort_by_key(|t| t.name);
+
+        let mut duplicates = Vec::new();
+        for t in &traits {
+            for pair in t.impls.windows(2) {
+                if pair[0] == pair[1]
+                    && duplicates.last() != Some(&(t.name, pair[0]))
+                {
+                    duplicates.push((t.name, pair[0]));
+                }
+            }
+        }
+
+        RegistryAudit {
+            trait_count: traits.len(),
+            impl_count: traits.iter().map(|t| t.impls.len()).sum(),
+            empty_tables: traits
+                .iter()
+                .filter(|t| t.impls.is_empty())
+                .map(|t| t.name)
+                .collect(),
+            duplicates,
+            traits,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+/*!
+This module defines `RegistryBuilder`, which builds a local `Registry` one
+trait at a time in a single expression, rather than by collecting a
+`CastIntoTrait` for each trait and inserting it. This is mostly useful in
+tests, and in code which deliberately avoids the global registry.
+*/
+use core::marker::PhantomData;
+
+use crate::prelude::*;
+use crate::{ImplEntry, Registry};
+
+/// Accumulates impls for a `Registry`.
+///
+/// Entries are made with `impl_entry!`, which performs the unsizing coercion
+/// from the concrete type into the trait object. That coercion can't be
+/// expressed with a generic method, so there is no `impl_type::<A>()` step.
+///
+/// # Example
+/// ```
+/// # use traitcast_core::builder::RegistryBuilder;
+/// # use traitcast_core::impl_entry;
+/// # use std::any::Any;
+/// trait Foo: traitcast_core::TraitcastFrom {}
+/// trait Bar: traitcast_core::TraitcastFrom {}
+///
+/// struct A;
+/// impl Foo for A {}
+/// impl Bar for A {}
+///
+/// struct B;
+/// impl Foo for B {}
+///
+/// let registry = RegistryBuilder::new()
+///     .trait_entry::<dyn Foo>()
+///     .entry(impl_entry!(dyn Foo, A))
+///     .entry(impl_entry!(dyn Foo, B))
+///     .trait_entry::<dyn Bar>()
+///     .entry(impl_entry!(dyn Bar, A))
+///     .build();
+///
+/// let b: &dyn Any = &B;
+/// assert!(registry.try_cast_ref::<_, dyn Foo>(b).is_ok());
+/// assert!(registry.try_cast_ref::<_, dyn Bar>(b).is_err());
+/// ```
+#[derive(Debug, Default)]
+pub struct RegistryBuilder {
+    registry: Registry,
+}
+
+/// A `RegistryBuilder` which is adding impls of the trait `DynTrait`. Made
+/// by `RegistryBuilder::trait_entry`.
+pub struct TraitEntryBuilder<DynTrait: ?Sized> {
+    builder: RegistryBuilder,
+    marker: PhantomData<fn() -> Box<DynTrait>>,
+}
+
+impl RegistryBuilder {
+    /// Starts building an empty registry.
+    pub fn new() -> RegistryBuilder {
+        RegistryBuilder::default()
+    }
+
+    /// Starts building on top of an existing registry. Entries for the same
+    /// concrete type and trait are replaced, as with `register_impl`.
+    pub fn from_registry(registry: Registry) -> RegistryBuilder {
+        RegistryBuilder { registry }
+    }
+
+    /// Starts adding impls of `DynTrait`. Its table is created even if no
+    /// impls are added, so that casting into it fails with
+    /// `ImplNotRegistered` rather than `TraitNotRegistered`.
+    pub fn trait_entry<DynTrait>(mut self) -> TraitEntryBuilder<DynTrait>
+    where
+        DynTrait: ?Sized + 'static,
+    {
+        self.registry.cast_into_or_insert::<DynTrait>();
+        TraitEntryBuilder {
+            builder: self,
+            marker: PhantomData,
+        }
+    }
+
+    /// Adds a single entry, for any trait.
+    pub fn entry<DynTrait>(mut self, entry: ImplEntry<DynTrait>) -> Self
+    where
+        DynTrait: ?Sized + 'static,
+    {
+        self.registry.register_impl(entry);
+        self
+    }
+
+    /// Finishes building the registry.
+    pub fn build(self) -> Registry {
+        self.registry
+    }
+}
+
+impl<DynTrait: ?Sized + 'static> TraitEntryBuilder<DynTrait> {
+    /// Adds an impl of `DynTrait`.
+    pub fn entry(mut self, entry: ImplEntry<DynTrait>) -> Self {
+        self.builder = self.builder.entry(entry);
+        self
+    }
+
+    /// Adds several impls of `DynTrait`.
+    pub fn entries<I>(mut self, entries: I) -> Self
+    where
+        I: IntoIterator<Item = ImplEntry<DynTrait>>,
+    {
+        self.builder.registry.register_all(entries);
+        self
+    }
+
+    /// Finishes adding impls of `DynTrait`, and starts adding impls of
+    /// `Other`.
+    pub fn trait_entry<Other>(self) -> TraitEntryBuilder<Other>
+    where
+        Other: ?Sized + 'static,
+    {
+        self.builder.trait_entry::<Other>()
+    }
+
+    /// Finishes adding impls of `DynTrait`, returning to the registry
+    /// builder.
+    pub fn done(self) -> RegistryBuilder {
+        self.builder
+    }
+
+    /// Finishes building the registry.
+    pub fn build(self) -> Registry {
+        self.builder.build()
+    }
+}
@@ -0,0 +1,25 @@
+/*!
+This module defines `CastClone`, which lets a registry clone values whose
+concrete type is only known at runtime.
+*/
+use core::any::Any;
+
+use crate::prelude::*;
+
+/// Clones a value into an owned `Box<dyn Any>`. This is blanket implemented
+/// for all `Clone` types, and registering `dyn CastClone` for a concrete type
+/// like any other trait is what allows values of that type to be cloned from
+/// behind a trait object.
+pub trait CastClone {
+    /// Clones the value into a new box.
+    fn clone_any(&self) -> Box<dyn Any>;
+}
+
+impl<T> CastClone for T
+where
+    T: Clone + Any,
+{
+    fn clone_any(&self) -> Box<dyn Any> {
+        Box::new(self.clone())
+    }
+}
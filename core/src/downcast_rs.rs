@@ -0,0 +1,61 @@
+/*!
+This module bridges `traitcast` with the `downcast-rs` crate, so that trait
+objects which already use `downcast_rs::Downcast` for their `Any` plumbing can
+be cast from without also implementing `TraitcastFrom`. Requires the
+`downcast_rs_compat` feature.
+*/
+use std::any::Any;
+use std::rc::Rc;
+
+pub use ::downcast_rs::Downcast;
+
+use crate::TraitcastFrom;
+
+impl TraitcastFrom for dyn Downcast {
+    fn as_any_ref(&self) -> &dyn Any {
+        Downcast::as_any(self)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        Downcast::as_any_mut(self)
+    }
+
+    fn as_any_box(self: Box<Self>) -> Box<dyn Any> {
+        Downcast::into_any(self)
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        Downcast::into_any_rc(self)
+    }
+}
+
+/// Implements `TraitcastFrom` for a trait object whose trait has `Downcast`
+/// as a supertrait, so that it can be cast from. Requires the
+/// `downcast_rs_compat` feature.
+///
+/// `traitcast_downcast_rs!(Widget)` must be invoked in the crate defining
+/// `Widget`, like `downcast_rs::impl_downcast!`.
+#[macro_export]
+macro_rules! traitcast_downcast_rs {
+    ($trait:path) => {
+        impl $crate::TraitcastFrom for dyn $trait {
+            fn as_any_ref(&self) -> &dyn std::any::Any {
+                $crate::downcast_rs::Downcast::as_any(self)
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+                $crate::downcast_rs::Downcast::as_any_mut(self)
+            }
+
+            fn as_any_box(self: Box<Self>) -> Box<dyn std::any::Any> {
+                $crate::downcast_rs::Downcast::into_any(self)
+            }
+
+            fn as_any_rc(
+                self: std::rc::Rc<Self>,
+            ) -> std::rc::Rc<dyn std::any::Any> {
+                $crate::downcast_rs::Downcast::into_any_rc(self)
+            }
+        }
+    };
+}
@@ -0,0 +1,88 @@
+/*!
+This module defines `FrozenRegistry`, an immutable registry whose casts are
+limited to an allowlist of concrete types, fixed when it is frozen. This bakes
+a cast policy into the registry itself, rather than relying on every caller to
+check it.
+*/
+use core::any::TypeId;
+use core::ops::Deref;
+
+#[cfg(not(feature = "std"))]
+use hashbrown::{HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+
+use crate::prelude::*;
+use crate::{Registry, TraitMeta};
+
+/// The concrete types which may be cast into each trait, by `TypeId`. A trait
+/// which is not mentioned allows no types at all.
+#[derive(Clone, Debug, Default)]
+pub struct Allowlist {
+    allowed: HashMap<TypeId, HashSet<TypeId>>,
+}
+
+impl Allowlist {
+    /// Makes a new allowlist, which allows nothing.
+    pub fn new() -> Self {
+        Allowlist::default()
+    }
+
+    /// Allows casting into `To` from the concrete type with `TypeId` `from`.
+    pub fn allow<To: ?Sized + 'static>(&mut self, from: TypeId) -> &mut Self {
+        self.allowed
+            .entry(TypeId::of::<To>())
+            .or_default()
+            .insert(from);
+        self
+    }
+
+    /// Allows casting into `To` from the concrete type `From`.
+    pub fn allow_type<To, From>(&mut self) -> &mut Self
+    where
+        To: ?Sized + 'static,
+        From: 'static,
+    {
+        self.allow::<To>(TypeId::of::<From>())
+    }
+
+    /// Whether casting the concrete type with `TypeId` `from` into the trait
+    /// object type with `TypeId` `into` is allowed.
+    pub fn is_allowed(&self, from: TypeId, into: TypeId) -> bool {
+        self.allowed
+            .get(&into)
+            .is_some_and(|allowed| allowed.contains(&from))
+    }
+}
+
+/// A registry which can no longer be modified, made by `Registry::freeze`. It
+/// dereferences to `Registry` for casting and introspection.
+pub struct FrozenRegistry {
+    registry: Registry,
+}
+
+impl Deref for FrozenRegistry {
+    type Target = Registry;
+
+    fn deref(&self) -> &Registry {
+        &self.registry
+    }
+}
+
+impl Registry {
+    /// Freezes the registry, removing every entry which the allowlist does
+    /// not allow. The tables themselves are kept, even if they end up empty,
+    /// so casting a type which was not allowed fails with `ImplNotRegistered`.
+    pub fn freeze(mut self, allowlist: &Allowlist) -> FrozenRegistry {
+        let metas: Vec<(TypeId, TraitMeta)> = self
+            .traits
+            .iter()
+            .map(|(&tid, &meta)| (tid, meta))
+            .collect();
+        for (into, meta) in metas {
+            (meta.retain)(&mut self, &|from| allowlist.is_allowed(from, into));
+        }
+        self.reverse_index = None;
+        FrozenRegistry { registry: self }
+    }
+}
@@ -4,20 +4,58 @@ This module defines helper types for using `traitcast` along with the
 */
 use crate::{CastIntoTrait, ImplEntry, Registry};
 
+/// A callback which is shown every entry as `EntryBuilder`s insert it, with
+/// the entry's `from_name` and `into_name`. Returning `false` vetoes the
+/// entry, so that it is left out of the registry.
+pub type RegistrationHook =
+    fn(from_name: &'static str, into_name: &'static str) -> bool;
+
+/// Inserts the entries of an `EntryBuilder` into a registry, leaving out those
+/// which the hook vetoes.
+pub type InsertFn = dyn Fn(&mut Registry, RegistrationHook) + Send + Sync;
+
+/// The hook used when none is given, which allows every entry.
+fn allow_all(_: &'static str, _: &'static str) -> bool {
+    true
+}
+
 /// Makes a trait registry by collecting EntryBuilders with the `inventory`
 /// crate.
 pub fn build_registry() -> Registry {
+    build_registry_with(allow_all)
+}
+
+/// Like `build_registry`, but calls the given hook for every entry before it
+/// is inserted. This is useful for logging or vetoing every impl the binary
+/// linked in.
+pub fn build_registry_with(hook: RegistrationHook) -> Registry {
     let mut reg = Registry::new();
-    for builder in inventory::iter::<EntryBuilder> {
-        (builder.insert)(&mut reg);
-    }
+    reg.load_inventory_filtered(hook);
     reg
 }
 
+impl Registry {
+    /// Applies every `EntryBuilder` collected by the `inventory` crate to this
+    /// registry, so that the impls registered with `traitcast!` are layered
+    /// on top of its existing contents. Entries for the same concrete type
+    /// and trait are replaced, as with `register_impl`.
+    pub fn load_inventory(&mut self) {
+        self.load_inventory_filtered(allow_all);
+    }
+
+    /// Like `load_inventory`, but leaves out every entry which the given hook
+    /// vetoes.
+    pub fn load_inventory_filtered(&mut self, hook: RegistrationHook) {
+        for builder in inventory::iter::<EntryBuilder> {
+            (builder.insert)(self, hook);
+        }
+    }
+}
+
 /// This is instantiated once for each castable trait. It describes how a trait
 /// can insert itself into the global table.
 pub struct EntryBuilder {
-    pub insert: Box<Fn(&mut Registry)>,
+    pub insert: Box<InsertFn>,
 }
 
 impl EntryBuilder {
@@ -30,34 +68,67 @@ impl EntryBuilder {
     {
         use std::iter::FromIterator;
         EntryBuilder {
-            insert: Box::new(|master| {
+            insert: Box::new(|master, hook| {
                 master.insert(CastIntoTrait::from_iter(
                     inventory::iter::<Entry>
                         .into_iter()
-                        .map(|x| x.as_ref().clone()),
+                        .map(|x| x.as_ref())
+                        .filter(|x| hook(x.from_name, x.into_name))
+                        .copied(),
                 ))
             }),
         }
     }
 
-    /// Constructs a trait builder that enters a single 'from' entry into the 
+    /// Constructs a trait builder that enters a single 'from' entry into the
     /// table for a particular target.
     ///
-    /// If the does not exist already, creates a new table. If it exists 
-    /// already, modifies the existing table by inserting the new entry.
+    /// If the does not exist already, creates a new table. If it exists
+    /// already, modifies the existing table by inserting the new entry. The
+    /// table holds at most one entry per concrete type, so registering the
+    /// same impl more than once is harmless.
+    ///
+    /// With the `strict_registration` feature, inserting an entry for a
+    /// concrete type which is already registered under different names
+    /// panics instead, naming both, since it usually means the impl was
+    /// registered twice through a type alias or path, e.g. after a merge.
+    /// Repeating the same registration, either by applying the same builder
+    /// twice or through an identical `traitcast!` invocation, is still fine.
     pub fn inserting_entry<To>(entry: ImplEntry<To>) -> EntryBuilder
     where
         To: 'static + ?Sized,
     {
         EntryBuilder {
-            insert: Box::new(move |master| {
-                let table: &mut CastIntoTrait<To> = 
-                    master.tables
-                    .entry::<CastIntoTrait<To>>()
-                    .or_insert(CastIntoTrait::new());
-
-                table.map.insert(entry.tid, entry.clone());
-            })
+            insert: Box::new(move |master, hook| {
+                let table: &mut CastIntoTrait<To> =
+                    master.cast_into_or_insert::<To>();
+
+                // Registering the same entry again is a no-op
+                let existing = table.get(entry.tid);
+                if existing == Some(&entry)
+                    || !hook(entry.from_name, entry.into_name)
+                {
+                    return;
+                }
+
+                #[cfg(feature = "strict_registration")]
+                if let Some(existing) = existing {
+                    if existing.from_name != entry.from_name
+                        || existing.into_name != entry.into_name
+                    {
+                        panic!(
+                            "conflicting registrations for casting '{}' into \
+                             '{}': it was already registered as casting '{}' \
+                             into '{}'",
+                            entry.into_name,
+                            entry.from_name,
+                            existing.into_name,
+                            existing.from_name,
+                        );
+                    }
+                }
+                table.map.insert(entry.tid, entry);
+            }),
         }
     }
 }
@@ -69,31 +140,160 @@ inventory::collect!(EntryBuilder);
 ///
 /// `traitcast!(struct Bar)` registers a struct to allow it to be cast into.
 ///
-/// `traitcast!(impl Foo for Bar)` allows casting into dynamic `Foo` trait 
-/// objects, from objects whose concrete type is `Bar`.
+/// `traitcast!(impl Foo for Bar)` allows casting into dynamic `Foo` trait
+/// objects, from objects whose concrete type is `Bar`. If `Bar` does not
+/// implement `Foo`, the error points at `Bar` as a missing bound.
 ///
-/// `traitcast!(struct Bar: Foo1, Foo2)` registers a struct to allow it to be 
+/// `traitcast!(struct Bar: Foo1, Foo2)` registers a struct to allow it to be
 /// cast into, and further allows casting into dynamic `Foo1` or `Foo2` trait
 /// objects, from objects whose concrete type is `Bar`.
+///
+/// `traitcast!(registered struct Bar: Foo1, Foo2)` does the same, and also
+/// implements `TraitcastRegistered` for `Bar`, listing `Foo1` and `Foo2`.
+/// Unlike the plain `struct` form, it can only be used once per struct, and
+/// only in the crate defining the struct.
+///
+/// `traitcast!(impl Foo1, Foo2; for Bar1, Bar2)` registers every impl of
+/// the listed traits for the listed types, as if `traitcast!(impl Foo; for
+/// Bar)` were used for each pair. Unlike the `struct` form, it does not
+/// register the types themselves.
+///
+/// `traitcast!(Bar => dyn Foo + Send)` allows casting into a trait object
+/// type with extra bounds, such as `dyn Foo + Send`, which is a different
+/// type with its own table from `dyn Foo`. The concrete type must satisfy the
+/// bounds too, or the registration does not compile.
+///
+/// Types may be any concrete type, including an instantiation of a generic
+/// struct, such as `traitcast!(impl Foo; for Wrapper<u32>)`. Each
+/// instantiation has its own `TypeId`, so each one must be registered, and
+/// its name is the type as written, e.g. `Wrapper<u32>`.
+///
+/// Every form accepts leading attributes, such as doc comments or `#[cfg]`,
+/// which are forwarded to each generated item. For example,
+/// `traitcast!(#[cfg(feature = "x")] impl Foo; for Bar)` only registers the
+/// impl when the invoking crate has feature `x` enabled.
 #[cfg(feature = "use_inventory")]
 #[macro_export]
 macro_rules! traitcast {
-    (struct $type:ty) => {
-        $crate::traitcast!($type => $type);
+    (@with [$($attrs:tt)*] $($rest:tt)*) => {
+        $crate::traitcast!($($attrs)* $($rest)*);
+    };
+    ($(#[$attr:meta])* struct $type:ty) => {
+        $crate::traitcast!($(#[$attr])* $type => $type);
+    };
+    ($(#[$attr:meta])* struct $type:ty : $($trait:path),+) => {
+        $crate::traitcast!($(#[$attr])* struct $type);
+        $crate::traitcast!(@impls [$(#[$attr])*] $type; $($trait),+);
+    };
+    ($(#[$attr:meta])* registered struct $type:ty : $($trait:path),+) => {
+        $crate::traitcast!($(#[$attr])* struct $type: $($trait),+);
+        $(#[$attr])*
+        impl $crate::TraitcastRegistered for $type {
+            const TRAITS: &'static [&'static str] =
+                &[$(stringify!(dyn $trait)),+];
+        }
+    };
+    (@impls $attrs:tt $type:ty; $($trait:path),+) => {
+        $(
+            $crate::traitcast!(@with $attrs impl $trait; for $type);
+        )+
+    };
+    ($(#[$attr:meta])* impl $trait:path; for $source:ty) => {
+        // Fails with a plain missing bound, rather than a failed coercion
+        // inside `impl_entry!`, if the impl does not exist
+        $(#[$attr])*
+        const _: fn() = || {
+            fn assert_impl<T: ?Sized + $trait>() {}
+            assert_impl::<$source>();
+        };
+        $crate::traitcast!($(#[$attr])* $source => dyn $trait);
+    };
+    ($(#[$attr:meta])* impl $($trait:path),+; for $($source:ty),+) => {
+        $crate::traitcast!(
+            @each [$(#[$attr])*] [$($trait),+]; $($source),+);
     };
-    (struct $type:ty : $($trait:path),+) => {
-        $crate::traitcast!(struct $type);
+    (@each $attrs:tt $traits:tt; $($source:ty),+) => {
         $(
-            $crate::traitcast!(impl $trait; for $type);
+            $crate::traitcast!(@each_trait $attrs $source; $traits);
         )+
     };
-    (impl $trait:path; for $source:ty) => {
-        $crate::traitcast!($source => dyn $trait);
+    (@each_trait $attrs:tt $type:ty; [$($trait:path),+]) => {
+        $crate::traitcast!(@impls $attrs $type; $($trait),+);
     };
-    ($source:ty => $target:ty) => {
+    ($(#[$attr:meta])* $source:ty => $target:ty) => {
+        $(#[$attr])*
         inventory::submit! {
             $crate::inventory::EntryBuilder::inserting_entry(
                 $crate::impl_entry!($target, $source))
         }
     };
 }
+
+/// Macro for registering an `enum_dispatch`-style enum with inventory.
+/// Requires the "use_inventory" feature.
+///
+/// `traitcast_enum_dispatch!(MyEnum: Foo1, Foo2)` registers the enum itself,
+/// and allows casting from objects whose concrete type is `MyEnum` into
+/// dynamic `Foo1` or `Foo2` trait objects, which is exactly what
+/// `enum_dispatch` generates impls for.
+///
+/// Note that the enum has a single `TypeId` regardless of which variant is
+/// active, so casts are decided by the enum's impls, not the variant's. To
+/// cast into a trait which only some variants implement, match on the enum
+/// and cast the variant's inner value instead.
+#[cfg(feature = "use_inventory")]
+#[macro_export]
+macro_rules! traitcast_enum_dispatch {
+    ($type:ty : $($trait:path),+) => {
+        $crate::traitcast!(struct $type: $($trait),+);
+    };
+}
+
+/// Macro for registering exactly one of two impls with inventory, depending
+/// on a `cfg` predicate. Requires the "use_inventory" feature.
+///
+/// `traitcast_cfg_impl!(cfg(feature = "x") => (Foo, A); else => (Foo, B))`
+/// behaves like `traitcast!(impl Foo; for A)` when the predicate holds, and
+/// like `traitcast!(impl Foo; for B)` otherwise. The predicate is evaluated
+/// in the crate invoking the macro.
+#[cfg(feature = "use_inventory")]
+#[macro_export]
+macro_rules! traitcast_cfg_impl {
+    (
+        cfg($($cfg:tt)*) => ($trait:path, $source:ty);
+        else => ($else_trait:path, $else_source:ty) $(;)?
+    ) => {
+        #[cfg($($cfg)*)]
+        $crate::traitcast!(impl $trait; for $source);
+        #[cfg(not($($cfg)*))]
+        $crate::traitcast!(impl $else_trait; for $else_source);
+    };
+}
+
+/// Deprecated alias kept for code written against the original README. Use
+/// `traitcast!(impl Foo; for Bar)` instead. Requires the "use_inventory"
+/// feature.
+///
+/// `register_impl!(Foo, Bar)` behaves exactly like
+/// `traitcast!(impl Foo; for Bar)`.
+#[cfg(feature = "use_inventory")]
+#[macro_export]
+macro_rules! register_impl {
+    ($trait:path, $source:ty $(,)?) => {
+        $crate::traitcast!(impl $trait; for $source);
+    };
+}
+
+/// Deprecated alias kept for code written against the original README.
+/// Requires the "use_inventory" feature.
+///
+/// `register_trait!(Foo, Foo_Traitcast)` used to define a wrapper type
+/// through which impls of `Foo` were registered. Tables are now created by
+/// the first impl registered for a trait, so this expands to nothing and the
+/// wrapper name is ignored. It can be deleted once every impl is registered
+/// with `traitcast!`.
+#[cfg(feature = "use_inventory")]
+#[macro_export]
+macro_rules! register_trait {
+    ($trait:path, $wrapper:ident $(,)?) => {};
+}
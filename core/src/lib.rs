@@ -3,36 +3,419 @@ This module is a general interface to `traitcast` which does not rely on a
 global registry. This makes it more flexible at the cost of having to create
 a registry and pass it around. If you do not want to do that, use the root
 `traitcast` module which provides a convenient global registry.
+
+Without the default `std` feature, this crate is `no_std`, and needs the
+`alloc` feature instead. Casting and the `Registry` API work the same either
+way, but the `inventory` integration requires `std`.
 */
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "nightly", feature(unsize))]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("traitcast_core requires either the `std` or `alloc` feature");
 
+extern crate alloc;
+
+pub mod audit;
+pub mod builder;
+pub mod clone;
+#[cfg(feature = "downcast_rs_compat")]
+pub mod downcast_rs;
+pub mod frozen;
 #[cfg(feature = "use_inventory")]
 pub mod inventory;
+#[cfg(feature = "nightly")]
+pub mod nightly;
+
+pub mod opcode;
+pub mod schema;
+pub mod static_dispatch;
+#[cfg(feature = "serde")]
+pub mod tagged;
+#[cfg(test)]
+pub mod tests;
+pub mod visitor;
 
-// #[cfg(test)]
-// pub mod tests;
+use core::any::{Any, TypeId};
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::pin::Pin;
 
-use std::any::{Any, TypeId};
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
 
+use crate::prelude::*;
+
+/// The parts of the `std` prelude which come from `alloc`, so that modules
+/// work the same with and without `std`.
+mod prelude {
+    pub(crate) use alloc::boxed::Box;
+    pub(crate) use alloc::rc::Rc;
+    pub(crate) use alloc::string::String;
+    pub(crate) use alloc::sync::Arc;
+    pub(crate) use alloc::vec;
+    pub(crate) use alloc::vec::Vec;
+}
+
+/// Paths used by the exported macros, which have to work in `no_std` crates
+/// as well. This is an implementation detail of `impl_entry!`.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::boxed::Box;
+    pub use alloc::rc::Rc;
+    pub use alloc::sync::Arc;
+    pub use core::marker::PhantomData;
+}
+
 /// A registry defining how to cast into some set of traits.
 pub struct Registry {
-    pub tables: anymap::Map<dyn anymap::any::Any + Sync>,
+    /// The `CastIntoTrait` for each trait, keyed by the `TypeId` of the trait
+    /// object.
+    tables: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    /// Type-erased information about each table in `tables`, with the same
+    /// keys. The tables themselves can't be inspected without knowing their
+    /// types, so this is what makes the registry introspectable.
+    traits: HashMap<TypeId, TraitMeta>,
+    /// Maps each concrete type to the traits it can be cast into, if it has
+    /// been built with `build_reverse_index` since the registry was last
+    /// modified.
+    reverse_index: Option<HashMap<TypeId, Vec<TypeId>>>,
+    /// Whether `try_cast_*` casts from a trait object into its own trait
+    /// without looking it up. See `set_identity_fast_path`.
+    identity_fast_path: bool,
+    /// Whether `try_cast_*` panics on casts that can only fail because of a
+    /// misconfiguration. See `set_strict`.
+    strict: bool,
+}
+
+/// Counts the entries registered by `Registry::register_all`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RegisterSummary {
+    /// Entries for concrete types which had no entry before.
+    pub inserted: usize,
+    /// Entries which replaced an existing entry for the same concrete type.
+    pub replaced: usize,
+}
+
+/// Type-erased information about the table for one trait in a `Registry`.
+#[derive(Clone, Copy)]
+pub(crate) struct TraitMeta {
+    pub(crate) name: &'static str,
+    pub(crate) impls: fn(&Registry) -> Vec<(TypeId, &'static str)>,
+    pub(crate) contains: fn(&Registry, TypeId) -> bool,
+    /// The name of the concrete type with the given `TypeId`, if it is in
+    /// the table.
+    pub(crate) name_of: fn(&Registry, TypeId) -> Option<&'static str>,
+    /// Moves the table out of the second registry into the first.
+    pub(crate) merge: fn(&mut Registry, &mut Registry),
+    pub(crate) schema: fn(&Registry) -> Vec<schema::EntrySchema>,
+    /// Keeps only the entries whose concrete type passes the predicate.
+    pub(crate) retain: fn(&mut Registry, &dyn Fn(TypeId) -> bool),
+}
+
+impl TraitMeta {
+    fn of<DynTrait: ?Sized + 'static>() -> Self {
+        TraitMeta {
+            name: core::any::type_name::<DynTrait>(),
+            impls: |registry| {
+                registry
+                    .cast_into::<DynTrait>()
+                    .map(|table| table.impls().collect())
+                    .unwrap_or_default()
+            },
+            contains: |registry, tid| {
+                registry
+                    .cast_into::<DynTrait>()
+                    .is_some_and(|table| table.contains(tid))
+            },
+            name_of: |registry, tid| {
+                let table = registry.cast_into::<DynTrait>()?;
+                table.get(tid).map(|entry| entry.into_name)
+            },
+            merge: |into, from| {
+                if let Some(table) = from.remove_trait::<DynTrait>() {
+                    into.cast_into_or_insert::<DynTrait>().extend(table);
+                }
+            },
+            schema: |registry| {
+                registry
+                    .cast_into::<DynTrait>()
+                    .map(|table| table.schema())
+                    .unwrap_or_default()
+            },
+            retain: |registry, keep| {
+                if let Some(table) = registry.cast_into_mut::<DynTrait>() {
+                    table.map.retain(|&tid, _| keep(tid));
+                }
+            },
+        }
+    }
 }
 
 impl Registry {
     /// Makes a new, empty trait registry.
     pub fn new() -> Registry {
         Registry {
-            tables: anymap::Map::new(),
+            tables: HashMap::new(),
+            traits: HashMap::new(),
+            reverse_index: None,
+            identity_fast_path: false,
+            strict: false,
         }
     }
 
+    /// Makes a new registry with empty tables for `dyn Display` and
+    /// `dyn Debug`, to be filled in with `register_std_traits!`. Casting into
+    /// `dyn Any` needs no table, so it works in any registry.
+    ///
+    /// Having the tables means that casting a type whose impls were not
+    /// registered fails with `ImplNotRegistered`, not `TraitNotRegistered`.
+    pub fn with_std_traits() -> Registry {
+        let mut registry = Registry::new();
+        registry.insert::<dyn core::fmt::Display>(CastIntoTrait::new());
+        registry.insert::<dyn core::fmt::Debug>(CastIntoTrait::new());
+        registry
+    }
+
     /// Updates the table defining how to cast into the given trait.
     pub fn insert<DynTrait: ?Sized + 'static>(
         &mut self,
         table: CastIntoTrait<DynTrait>,
     ) {
-        self.tables.insert(table);
+        self.record::<DynTrait>();
+        self.tables
+            .insert(TypeId::of::<DynTrait>(), Box::new(table));
+    }
+
+    /// Inserts a single entry into the table for its trait, creating the table
+    /// if needed. This overwrites any existing entry for the same concrete
+    /// type, and returns whether there was one. Together with `impl_entry!`,
+    /// this allows registering impls at runtime, e.g. from dynamically loaded
+    /// plugins.
+    pub fn register_impl<DynTrait: ?Sized + 'static>(
+        &mut self,
+        entry: ImplEntry<DynTrait>,
+    ) -> bool {
+        self.cast_into_or_insert::<DynTrait>()
+            .map
+            .insert(entry.tid, entry)
+            .is_some()
+    }
+
+    /// Registers every entry with `register_impl`, and counts how many were
+    /// new and how many replaced an existing entry, e.g. to catch accidental
+    /// overwrites when building a registry by hand.
+    pub fn register_all<DynTrait, I>(&mut self, entries: I) -> RegisterSummary
+    where
+        DynTrait: ?Sized + 'static,
+        I: IntoIterator<Item = ImplEntry<DynTrait>>,
+    {
+        let mut summary = RegisterSummary::default();
+        for entry in entries {
+            if self.register_impl(entry) {
+                summary.replaced += 1;
+            } else {
+                summary.inserted += 1;
+            }
+        }
+        summary
+    }
+
+    /// Removes the whole table for the given trait, returning it if there was
+    /// one.
+    ///
+    /// Entries hold function pointers into the code that registered them, so
+    /// when a dynamically loaded plugin is unloaded, every entry it
+    /// registered must be removed first. Casting through an entry after the
+    /// library it points into has been closed is undefined behavior.
+    pub fn remove_trait<DynTrait: ?Sized + 'static>(
+        &mut self,
+    ) -> Option<CastIntoTrait<DynTrait>> {
+        self.reverse_index = None;
+        self.traits.remove(&TypeId::of::<DynTrait>());
+        let table = self.tables.remove(&TypeId::of::<DynTrait>())?;
+        Some(
+            *table
+                .downcast::<CastIntoTrait<DynTrait>>()
+                .expect(TABLE_TYPE),
+        )
+    }
+
+    /// Removes the entry for casting the concrete type with the given
+    /// `TypeId` into the given trait, returning it if there was one. The
+    /// table itself is kept, even if it becomes empty.
+    ///
+    /// As with `remove_trait`, a plugin's entries must be removed before the
+    /// plugin is unloaded, or casting through them is undefined behavior.
+    pub fn remove_impl<DynTrait: ?Sized + 'static>(
+        &mut self,
+        concrete: TypeId,
+    ) -> Option<ImplEntry<DynTrait>> {
+        self.cast_into_mut::<DynTrait>()?.map.remove(&concrete)
+    }
+
+    /// Merges every table of another registry into this one. Tables for traits
+    /// which this registry doesn't know about yet are moved in as they are.
+    /// Otherwise, the entries are combined as by `CastIntoTrait::extend`, so
+    /// where both registries have an entry for the same concrete type, the
+    /// entry from `other` wins.
+    pub fn merge(&mut self, mut other: Registry) {
+        let metas: Vec<TraitMeta> = other.traits.values().copied().collect();
+        for meta in metas {
+            (meta.merge)(self, &mut other);
+        }
+    }
+
+    /// Gets the table defining how to cast into the given trait, inserting an
+    /// empty one first if it does not exist yet.
+    pub(crate) fn cast_into_or_insert<DynTrait: ?Sized + 'static>(
+        &mut self,
+    ) -> &mut CastIntoTrait<DynTrait> {
+        self.record::<DynTrait>();
+        self.tables
+            .entry(TypeId::of::<DynTrait>())
+            .or_insert_with(|| Box::new(CastIntoTrait::<DynTrait>::new()))
+            .downcast_mut()
+            .expect(TABLE_TYPE)
+    }
+
+    /// Records the metadata for a table which is about to be modified.
+    fn record<DynTrait: ?Sized + 'static>(&mut self) {
+        self.reverse_index = None;
+        self.traits
+            .entry(TypeId::of::<DynTrait>())
+            .or_insert_with(TraitMeta::of::<DynTrait>);
+    }
+
+    /// Lists the names of the concrete types registered for the given trait,
+    /// or returns `None` if the trait has no table.
+    pub fn impls_for<To>(&self) -> Option<Vec<&'static str>>
+    where
+        To: ?Sized + 'static,
+    {
+        let table = self.cast_into::<To>()?;
+        Some(table.impls().map(|(_, name)| name).collect())
+    }
+
+    /// Iterates over the `TypeId`s of the trait objects which have a table in
+    /// the registry, in no particular order.
+    pub fn registered_traits(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.traits.keys().copied()
+    }
+
+    /// Iterates over the names of the trait objects which have a table in the
+    /// registry, in the same order as `registered_traits`.
+    pub fn registered_trait_names(
+        &self,
+    ) -> impl Iterator<Item = &'static str> + '_ {
+        self.traits.values().map(|meta| meta.name)
+    }
+
+    /// Checks whether the given trait has a table in the registry.
+    pub fn is_trait_registered<To>(&self) -> bool
+    where
+        To: ?Sized + 'static,
+    {
+        self.cast_into::<To>().is_some()
+    }
+
+    /// Checks whether the concrete type with `TypeId` `concrete` can be cast
+    /// into the trait object type with `TypeId` `into`.
+    pub fn can_cast(&self, concrete: TypeId, into: TypeId) -> bool {
+        self.traits
+            .get(&into)
+            .is_some_and(|meta| (meta.contains)(self, concrete))
+    }
+
+    /// Checks whether every cast which `other` has an entry for, from a
+    /// concrete type into a trait, also has an entry in this registry. This
+    /// only compares pairs, so empty tables in `other` don't matter.
+    pub fn is_superset_of(&self, other: &Registry) -> bool {
+        other.traits.iter().all(|(&into, meta)| {
+            (meta.impls)(other)
+                .into_iter()
+                .all(|(concrete, _)| self.can_cast(concrete, into))
+        })
+    }
+
+    /// Checks whether the registry has a table for the trait object type with
+    /// the given `TypeId`.
+    pub fn has_trait(&self, into: TypeId) -> bool {
+        self.traits.contains_key(&into)
+    }
+
+    /// Checks whether there is an entry for casting the concrete type with
+    /// the given `TypeId` into the given trait.
+    ///
+    /// Rust function pointers can never be null, so an entry that exists is
+    /// always callable; there is no way to build one with a null `cast_ref`
+    /// without already having undefined behavior. Entries which come from FFI
+    /// should be validated before they are turned into an `ImplEntry`.
+    pub fn probe<To>(&self, tid: TypeId) -> bool
+    where
+        To: ?Sized + 'static,
+    {
+        self.cast_into::<To>()
+            .is_some_and(|table| table.contains(tid))
+    }
+
+    /// Lists the `TypeId`s of the traits which the concrete type with the
+    /// given `TypeId` can be cast into, in no particular order.
+    ///
+    /// This scans every table, unless the reverse index has been built with
+    /// `build_reverse_index`.
+    pub fn traits_for(&self, concrete: TypeId) -> Vec<TypeId> {
+        if let Some(index) = &self.reverse_index {
+            return index.get(&concrete).cloned().unwrap_or_default();
+        }
+        self.traits
+            .iter()
+            .filter(|(_, meta)| (meta.contains)(self, concrete))
+            .map(|(tid, _)| *tid)
+            .collect()
+    }
+
+    /// Like `traits_for`, but lists the names of the trait object types.
+    pub fn trait_names_for(&self, concrete: TypeId) -> Vec<&'static str> {
+        self.traits_for(concrete)
+            .into_iter()
+            .map(|tid| self.traits[&tid].name)
+            .collect()
+    }
+
+    /// Builds an index from each concrete type to the traits it can be cast
+    /// into, which speeds up `traits_for` on hot paths. The index is cached
+    /// on the registry, and dropped again whenever the registry is modified.
+    pub fn build_reverse_index(&mut self) -> &HashMap<TypeId, Vec<TypeId>> {
+        let mut index: HashMap<TypeId, Vec<TypeId>> = HashMap::new();
+        for (tid, meta) in &self.traits {
+            for (concrete, _) in (meta.impls)(self) {
+                index.entry(concrete).or_default().push(*tid);
+            }
+        }
+        self.reverse_index.get_or_insert(index)
+    }
+
+    /// Gets the name of the concrete type with the given `TypeId`, as given to
+    /// `impl_entry!`, if any table in the registry has an entry for it. This
+    /// is useful for reporting a failed cast given only `(*x).type_id()`.
+    pub fn name_of(&self, tid: TypeId) -> Option<&'static str> {
+        self.trait_metas()
+            .find_map(|meta| (meta.name_of)(self, tid))
+    }
+
+    /// Counts the traits which the concrete type with the given `TypeId` can
+    /// be cast into.
+    pub fn trait_count_for(&self, tid: TypeId) -> usize {
+        self.trait_metas()
+            .filter(|meta| (meta.contains)(self, tid))
+            .count()
+    }
+
+    /// Iterates over the type-erased information about each table.
+    pub(crate) fn trait_metas(&self) -> impl Iterator<Item = &TraitMeta> {
+        self.traits.values()
     }
 
     /// Gets the table defining how to cast into the given trait.
@@ -55,24 +438,467 @@ impl Registry {
     where
         To: ?Sized + 'static,
     {
-        self.tables.get::<CastIntoTrait<To>>()
+        let table = self.tables.get(&TypeId::of::<To>())?;
+        Some(table.downcast_ref().expect(TABLE_TYPE))
+    }
+
+    /// Gets a handle to the table defining how to cast into the given trait,
+    /// if it has been inserted already. The handle borrows the table, so the
+    /// trait lookup can be hoisted out of a loop, leaving only the lookup for
+    /// the concrete type in each cast.
+    ///
+    /// # Examples
+    /// ```text
+    /// let table = registry.table::<dyn Foo>()?;
+    /// for x in &xs {
+    ///     table.from_ref(&**x)
+    /// }
+    /// ```
+    pub fn table<To>(&self) -> Option<TraitTable<'_, To>>
+    where
+        To: ?Sized + 'static,
+    {
+        self.cast_into::<To>().map(|table| TraitTable { table })
+    }
+
+    /// Gets mutable access to the table defining how to cast into the given
+    /// trait, if it has been inserted already.
+    ///
+    /// # Examples
+    /// ```text
+    /// registry
+    ///     .cast_into_mut::<Foo>()?
+    ///     .map
+    ///     .insert(entry.tid, entry);
+    /// ```
+    pub fn cast_into_mut<To>(&mut self) -> Option<&mut CastIntoTrait<To>>
+    where
+        To: ?Sized + 'static,
+    {
+        self.reverse_index = None;
+        let table = self.tables.get_mut(&TypeId::of::<To>())?;
+        Some(table.downcast_mut().expect(TABLE_TYPE))
+    }
+
+    /// Like `cast_into`, but fails with `TraitNotRegistered` if there is no
+    /// table for the given trait, so that the failure can be reported.
+    pub fn cast_into_checked<To>(&self) -> Result<&CastIntoTrait<To>, CastError>
+    where
+        To: ?Sized + 'static,
+    {
+        self.cast_into::<To>().ok_or(CastError::TraitNotRegistered {
+            into_name: core::any::type_name::<To>(),
+        })
+    }
+
+    /// Sets whether `try_cast_ref`, `try_cast_mut` and `try_cast_box` cast
+    /// from a type into itself, e.g. from `dyn Foo` into `dyn Foo`, without
+    /// looking it up. With the fast path, such casts always succeed. Without
+    /// it, which is the default, they need the concrete type to be
+    /// registered for the trait like any other cast, which catches missing
+    /// registrations even in generic code that happens to cast a trait
+    /// object into its own trait.
+    ///
+    /// Casts between different traits are unaffected.
+    pub fn set_identity_fast_path(&mut self, enabled: bool) {
+        self.identity_fast_path = enabled;
+    }
+
+    /// Whether the identity fast path is enabled. See
+    /// `set_identity_fast_path`.
+    pub fn identity_fast_path(&self) -> bool {
+        self.identity_fast_path
+    }
+
+    /// Sets whether `try_cast_ref`, `try_cast_mut` and `try_cast_box` panic,
+    /// instead of returning an error, when casting into a trait which has no
+    /// table or whose table is empty. Such a cast can never succeed, so it
+    /// usually means a registration is missing, which strict mode reports at
+    /// the cast that noticed it. A value which is simply not registered for
+    /// a trait with other impls still fails with `ImplNotRegistered`.
+    ///
+    /// Strict mode is off by default, and is meant to be turned on during
+    /// development and in tests.
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    /// Whether strict mode is enabled. See `set_strict`.
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Gets the table used by `try_cast_*`, panicking in strict mode if the
+    /// cast can only fail.
+    fn try_cast_table<To>(&self) -> Result<&CastIntoTrait<To>, CastError>
+    where
+        To: ?Sized + 'static,
+    {
+        let table = self.cast_into_checked::<To>();
+        if let Err(err) = &table {
+            trace_cast_failure(err);
+        }
+        if self.strict {
+            match table {
+                Err(err) => panic!(
+                    "strict traitcast registry: {}; register an impl of it \
+                     before casting into it",
+                    err
+                ),
+                Ok(table) if table.map.is_empty() => panic!(
+                    "strict traitcast registry: trait '{}' is registered, \
+                     but has no impls, so casting into it always fails",
+                    core::any::type_name::<To>()
+                ),
+                Ok(_) => {}
+            }
+        }
+        table
+    }
+
+    /// Tries to cast the given reference to a dynamic trait object, reporting
+    /// why the cast failed if it does.
+    ///
+    /// Casting into `dyn Any` always succeeds without a table, since every
+    /// castable type is `Any`. The same goes for `try_cast_mut` and
+    /// `try_cast_box`.
+    pub fn try_cast_ref<'a, From, To>(
+        &self,
+        x: &'a From,
+    ) -> Result<&'a To, CastError>
+    where
+        From: TraitcastFrom + ?Sized + 'static,
+        To: ?Sized + 'static,
+    {
+        if self.identity_fast_path {
+            if let Some(x) = identity_ref(x) {
+                return Ok(x);
+            }
+        }
+
+        let x = x.as_any_ref();
+        if let Some(x) = identity_ref::<dyn Any, To>(x) {
+            return Ok(x);
+        }
+
+        let table = self.try_cast_table::<To>()?;
+        let from = (*x).type_id();
+        table
+            .from_ref(x)
+            .ok_or_else(|| impl_not_registered::<To>(from))
+    }
+
+    /// Tries to cast the given mutable reference to a dynamic trait object,
+    /// reporting why the cast failed if it does.
+    pub fn try_cast_mut<'a, From, To>(
+        &self,
+        x: &'a mut From,
+    ) -> Result<&'a mut To, CastError>
+    where
+        From: TraitcastFrom + ?Sized + 'static,
+        To: ?Sized + 'static,
+    {
+        let x = if self.identity_fast_path {
+            match identity_mut(x) {
+                Ok(x) => return Ok(x),
+                Err(x) => x,
+            }
+        } else {
+            x
+        };
+
+        let x = match identity_mut::<dyn Any, To>(x.as_any_mut()) {
+            Ok(x) => return Ok(x),
+            Err(x) => x,
+        };
+
+        let table = self.try_cast_table::<To>()?;
+        let from = (*x).type_id();
+        table
+            .from_mut(x)
+            .ok_or_else(|| impl_not_registered::<To>(from))
+    }
+
+    /// Tries to cast the given pointer to a dynamic trait object, reporting
+    /// why the cast failed if it does. The box is dropped on failure; use
+    /// `cast_into_checked` and `from_box` to get it back instead.
+    pub fn try_cast_box<From, To>(
+        &self,
+        x: Box<From>,
+    ) -> Result<Box<To>, CastError>
+    where
+        From: TraitcastFrom + ?Sized + 'static,
+        To: ?Sized + 'static,
+    {
+        let x = if self.identity_fast_path {
+            match identity_box(x) {
+                Ok(x) => return Ok(x),
+                Err(x) => x,
+            }
+        } else {
+            x
+        };
+
+        let x = match identity_box::<dyn Any, To>(x.as_any_box()) {
+            Ok(x) => return Ok(x),
+            Err(x) => x,
+        };
+
+        let table = self.try_cast_table::<To>()?;
+        let from = (*x).type_id();
+        table
+            .from_box(x)
+            .map_err(|_| impl_not_registered::<To>(from))
     }
 }
 
+/// Returns `x` as a `&To` if `From` and `To` are the same type. This is an
+/// implementation detail of the cast functions of `traitcast`.
+#[doc(hidden)]
+pub fn identity_ref<From, To>(x: &From) -> Option<&To>
+where
+    From: ?Sized + 'static,
+    To: ?Sized + 'static,
+{
+    if TypeId::of::<From>() == TypeId::of::<To>() {
+        // Safety: `From` and `To` are the same type, so this only changes the
+        // type of the reference in name
+        Some(unsafe { core::mem::transmute_copy::<&From, &To>(&x) })
+    } else {
+        None
+    }
+}
+
+/// Returns `x` as a `&mut To` if `From` and `To` are the same type, or gives
+/// it back otherwise. This is an implementation detail of the cast
+/// functions of `traitcast`.
+#[doc(hidden)]
+pub fn identity_mut<From, To>(x: &mut From) -> Result<&mut To, &mut From>
+where
+    From: ?Sized + 'static,
+    To: ?Sized + 'static,
+{
+    if TypeId::of::<From>() == TypeId::of::<To>() {
+        // Safety: as in `identity_ref`. `x` is not used again, so the result
+        // is the only mutable reference
+        Ok(unsafe { core::mem::transmute_copy::<&mut From, &mut To>(&x) })
+    } else {
+        Err(x)
+    }
+}
+
+/// Returns `x` as a `Box<To>` if `From` and `To` are the same type, or gives
+/// it back otherwise. This is an implementation detail of the cast
+/// functions of `traitcast`.
+#[doc(hidden)]
+pub fn identity_box<From, To>(x: Box<From>) -> Result<Box<To>, Box<From>>
+where
+    From: ?Sized + 'static,
+    To: ?Sized + 'static,
+{
+    if TypeId::of::<From>() == TypeId::of::<To>() {
+        let x = Box::into_raw(x);
+        // Safety: as in `identity_ref`. The pointer came from `Box::into_raw`
+        // and is owned by the new box alone
+        Ok(unsafe {
+            Box::from_raw(core::mem::transmute_copy::<*mut From, *mut To>(&x))
+        })
+    } else {
+        Err(x)
+    }
+}
+
+fn impl_not_registered<To: ?Sized>(from: TypeId) -> CastError {
+    CastError::ImplNotRegistered {
+        from,
+        into_name: core::any::type_name::<To>(),
+    }
+}
+
+/// Emits a trace-level `tracing` event for a failed cast, recording why it
+/// failed and the types involved. Without the `tracing` feature this does
+/// nothing, and compiles away entirely. This is an implementation detail of
+/// the cast functions of `traitcast`.
+#[doc(hidden)]
+#[inline(always)]
+pub fn trace_cast_failure(err: &CastError) {
+    #[cfg(feature = "tracing")]
+    match *err {
+        CastError::TraitNotRegistered { into_name } => tracing::trace!(
+            into_name,
+            reason = "trait not registered",
+            "traitcast: cast failed"
+        ),
+        CastError::ImplNotRegistered { from, into_name } => tracing::trace!(
+            ?from,
+            into_name,
+            reason = "impl not registered",
+            "traitcast: cast failed"
+        ),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = err;
+}
+
+/// The message for the impossible failure to downcast a table of a
+/// `Registry`, which is always stored under the `TypeId` of its trait.
+const TABLE_TYPE: &str = "registry table stored under the wrong TypeId";
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+/// Lists the names of the concrete types registered for each trait, sorted,
+/// so that the output is stable.
+impl core::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let mut traits: Vec<(&'static str, Vec<&'static str>)> = self
+            .trait_metas()
+            .map(|meta| {
+                let mut impls: Vec<&'static str> = (meta.impls)(self)
+                    .into_iter()
+                    .map(|(_, name)| name)
+                    .collect();
+                impls.sort_unstable();
+                (meta.name, impls)
+            })
+            .collect();
+        traits.sort_unstable();
+
+        let mut out = f.debug_struct("Registry");
+        for (name, impls) in &traits {
+            out.field(name, impls);
+        }
+        out.finish()
+    }
+}
+
+/// The hasher that `CastIntoTrait` uses unless told otherwise. With `std`,
+/// this is the standard library's default `RandomState`, which resists
+/// collision attacks but costs more per hash than `TypeId`s need; the
+/// `fast_hash` feature swaps it for `FxHasher`.
+#[cfg(all(feature = "std", not(feature = "fast_hash")))]
+pub type DefaultHashBuilder = std::collections::hash_map::RandomState;
+
+/// The hasher that `CastIntoTrait` uses unless told otherwise. Without `std`,
+/// this is the default hasher of `hashbrown`.
+#[cfg(all(not(feature = "std"), not(feature = "fast_hash")))]
+pub type DefaultHashBuilder = hashbrown::DefaultHashBuilder;
+
+/// The hasher that `CastIntoTrait` uses unless told otherwise. `TypeId`s are
+/// already well distributed, so with the `fast_hash` feature this is the much
+/// cheaper `FxHasher` rather than the standard library's default.
+#[cfg(feature = "fast_hash")]
+pub type DefaultHashBuilder =
+    core::hash::BuildHasherDefault<rustc_hash::FxHasher>;
+
+/// The number of impls up to which `CastIntoTrait` finds entries by scanning
+/// its map rather than by hashing. A scan never hashes, so it wins for the
+/// handful of impls most traits have; `benches/small_table.rs` in `traitcast`
+/// compares the two, along with a sorted `Vec`, which is no faster than the
+/// scan for small tables.
+///
+/// This is 8 by default. With the `fast_hash` feature it is 2 instead, since
+/// `FxHasher` is cheap enough that scanning only wins for the smallest
+/// tables.
+pub const SMALL_TABLE_CUTOFF: usize =
+    if cfg!(feature = "fast_hash") { 2 } else { 8 };
+
 /// Provides methods for casting into the target trait object from other trait
 /// objects.
-pub struct CastIntoTrait<DynTrait: ?Sized> {
-    pub map: HashMap<TypeId, ImplEntry<DynTrait>>,
+///
+/// Tables stored in a `Registry` always use `DefaultHashBuilder`.
+pub struct CastIntoTrait<DynTrait: ?Sized, S = DefaultHashBuilder> {
+    pub map: HashMap<TypeId, ImplEntry<DynTrait>, S>,
 }
 
 impl<DynTrait: ?Sized> CastIntoTrait<DynTrait> {
     pub fn new() -> Self {
-        CastIntoTrait { map: HashMap::new() }
+        CastIntoTrait {
+            map: HashMap::default(),
+        }
+    }
+}
+
+impl<DynTrait: ?Sized, S: BuildHasher> CastIntoTrait<DynTrait, S> {
+    /// Makes an empty table which uses the given hasher.
+    pub fn with_hasher(hasher: S) -> Self {
+        CastIntoTrait {
+            map: HashMap::with_hasher(hasher),
+        }
+    }
+
+    /// Iterates over the `TypeId` and name of each concrete type registered
+    /// in the table, in no particular order.
+    pub fn impls(&self) -> impl Iterator<Item = (TypeId, &'static str)> + '_ {
+        self.map.values().map(|entry| (entry.tid, entry.into_name))
+    }
+
+    /// Inserts an entry, returning the entry it replaced for the same concrete
+    /// type, if there was one.
+    pub fn insert(
+        &mut self,
+        entry: ImplEntry<DynTrait>,
+    ) -> Option<ImplEntry<DynTrait>> {
+        self.map.insert(entry.tid, entry)
+    }
+
+    /// The number of concrete types registered in the table.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether no concrete types are registered in the table.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Iterates over the entries in the table, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &ImplEntry<DynTrait>> + '_ {
+        self.map.values()
+    }
+
+    /// Merges the entries of another table for the same trait into this one.
+    /// Where both tables have an entry for the same concrete type, the entry
+    /// from `other` wins.
+    pub fn extend(&mut self, other: CastIntoTrait<DynTrait, S>) {
+        self.map.extend(other.map);
+    }
+}
+
+/// Lists the name and `TypeId` of each concrete type in the table, sorted by
+/// name.
+impl<DynTrait: ?Sized, S> core::fmt::Debug for CastIntoTrait<DynTrait, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let mut entries: Vec<(&'static str, TypeId)> = self
+            .map
+            .values()
+            .map(|entry| (entry.into_name, entry.tid))
+            .collect();
+        entries.sort_unstable();
+
+        let mut out = f.debug_struct("CastIntoTrait");
+        for (name, tid) in &entries {
+            out.field(name, tid);
+        }
+        out.finish()
+    }
+}
+
+impl<DynTrait: ?Sized, S: Default> Default for CastIntoTrait<DynTrait, S> {
+    fn default() -> Self {
+        CastIntoTrait {
+            map: HashMap::default(),
+        }
     }
 }
 
-impl<DynTrait: ?Sized> std::iter::FromIterator<ImplEntry<DynTrait>>
-    for CastIntoTrait<DynTrait>
+impl<DynTrait: ?Sized, S> core::iter::FromIterator<ImplEntry<DynTrait>>
+    for CastIntoTrait<DynTrait, S>
+where
+    S: BuildHasher + Default,
 {
     fn from_iter<T>(iter: T) -> Self
     where
@@ -84,37 +910,84 @@ impl<DynTrait: ?Sized> std::iter::FromIterator<ImplEntry<DynTrait>>
     }
 }
 
-impl<To: ?Sized + 'static> CastIntoTrait<To> {
+impl<To: ?Sized + 'static, S: BuildHasher> CastIntoTrait<To, S> {
+    /// Looks up the entry for the concrete type with the given `TypeId`. Small
+    /// tables are scanned, which is faster than hashing the `TypeId`.
+    fn entry(&self, tid: TypeId) -> Option<&ImplEntry<To>> {
+        if self.map.len() <= SMALL_TABLE_CUTOFF {
+            self.map
+                .iter()
+                .find(|(key, _)| **key == tid)
+                .map(|(_, entry)| entry)
+        } else {
+            self.map.get(&tid)
+        }
+    }
+
+    /// Like `get`, but traces the failure if there is no entry. Used by every
+    /// cast through the table, including the global casts in `traitcast`.
+    #[doc(hidden)]
+    pub fn entry_or_trace(&self, tid: TypeId) -> Option<&ImplEntry<To>> {
+        let entry = self.entry(tid);
+        if entry.is_none() {
+            trace_cast_failure(&impl_not_registered::<To>(tid));
+        }
+        entry
+    }
+
+    /// Gets the entry for the concrete type with the given `TypeId`, if it is
+    /// registered.
+    pub fn get(&self, tid: TypeId) -> Option<&ImplEntry<To>> {
+        self.entry(tid)
+    }
+
+    /// Whether the concrete type with the given `TypeId` is registered.
+    pub fn contains(&self, tid: TypeId) -> bool {
+        self.entry(tid).is_some()
+    }
+
+    /// Gets the entry for the concrete type with the given name, as given to
+    /// `impl_entry!`. Names are not indexed, so this scans the whole table.
+    pub fn get_by_name(&self, name: &str) -> Option<&ImplEntry<To>> {
+        self.map.values().find(|entry| entry.into_name == name)
+    }
+
     /// Tries to cast the given reference to a dynamic trait object. This will
     /// always return None if the implementation of the target trait, for the
-    /// concrete type of x, has not been registered via `traitcast_to_impl!`.
+    /// concrete type of x, has not been registered via `traitcast!`.
     pub fn from_ref<'a, From>(&self, x: &'a From) -> Option<&'a To>
     where
         From: TraitcastFrom + ?Sized,
     {
         let x = (*x).as_any_ref();
         let tid = x.type_id();
-        let s = self.map.get(&tid)?;
+        let s = self.entry_or_trace(tid)?;
         (s.cast_ref)(x)
     }
 
     /// Tries to cast the given mutable reference to a dynamic trait object.
     /// This will always return None if the implementation of the target trait,
-    /// for the concrete type of x, has not been registered via
-    /// `traitcast_to_impl!`.
+    /// for the concrete type of x, has not been registered via `traitcast!`.
     pub fn from_mut<'a, From>(&self, x: &'a mut From) -> Option<&'a mut To>
     where
         From: TraitcastFrom + ?Sized,
     {
         let x = (*x).as_any_mut();
         let tid = (x as &dyn Any).type_id();
-        let s = self.map.get(&tid)?;
+        let s = self.entry_or_trace(tid)?;
         (s.cast_mut)(x)
     }
 
     /// Tries to cast the given pointer to a dynamic trait object. This will
     /// always return Err if the implementation of the target trait, for the
-    /// concrete type of x, has not been registered via `traitcast_to_impl!`.
+    /// concrete type of x, has not been registered via `traitcast!`.
+    ///
+    /// This holds even when casting a `Box<dyn Foo>` into `dyn Foo`. For a
+    /// cast which always succeeds in that case, enable the registry's
+    /// identity fast path and use `Registry::try_cast_box`.
+    ///
+    /// The cast reuses the allocation of `x` without allocating, both on
+    /// success and on failure.
     pub fn from_box<From>(&self, x: Box<From>) -> Result<Box<To>, Box<dyn Any>>
     where
         From: TraitcastFrom + ?Sized,
@@ -125,40 +998,323 @@ impl<To: ?Sized + 'static> CastIntoTrait<To> {
         // id of the box itself.
         let tid = (*x).type_id();
 
-        let s = match self.map.get(&tid) {
+        let s = match self.entry_or_trace(tid) {
             Some(s) => s,
             None => return Err(x),
         };
 
         (s.cast_box)(x)
     }
+
+    /// Tries to cast the given pinned box to a pinned dynamic trait object.
+    /// This will always return Err if the implementation of the target trait,
+    /// for the concrete type of x, has not been registered via `traitcast!`.
+    pub fn from_pin_box<From>(
+        &self,
+        x: Pin<Box<From>>,
+    ) -> Result<Pin<Box<To>>, Pin<Box<dyn Any>>>
+    where
+        From: TraitcastFrom + ?Sized,
+    {
+        // Safety: casting only replaces the vtable half of the box pointer,
+        // and the box is re-pinned straight away whichever way the cast
+        // goes, so the pointee is never moved or exposed unpinned.
+        let x = unsafe { Pin::into_inner_unchecked(x) };
+        match self.from_box(x) {
+            Ok(x) => Ok(Box::into_pin(x)),
+            Err(x) => Err(Box::into_pin(x)),
+        }
+    }
+
+    /// Like `from_box`, but keeps the `Send + Sync` bounds of the box on
+    /// failure. On success they are kept if `To` has them, e.g. for a table of
+    /// `dyn Foo + Send + Sync`.
+    pub fn from_box_send<From>(
+        &self,
+        x: Box<From>,
+    ) -> Result<Box<To>, Box<dyn Any + Send + Sync>>
+    where
+        From: TraitcastFromSend + ?Sized,
+    {
+        let x = x.as_any_box_send();
+        let tid = (*x).type_id();
+
+        let s = match self.entry_or_trace(tid) {
+            Some(s) => s,
+            None => return Err(x),
+        };
+
+        (s.cast_box_send)(x)
+    }
+
+    /// Tries to cast the given atomically reference-counted pointer to a
+    /// dynamic trait object. This will always return Err if the
+    /// implementation of the target trait, for the concrete type of x, has
+    /// not been registered via `traitcast!`.
+    ///
+    /// The pointer is never cloned: on success the strong count is
+    /// transferred to the result, and on failure the original pointer is
+    /// handed back, so the strong count is unchanged either way.
+    pub fn from_arc<From>(
+        &self,
+        x: Arc<From>,
+    ) -> Result<Arc<To>, Arc<dyn Any + Send + Sync>>
+    where
+        From: TraitcastFromSend + ?Sized,
+    {
+        let x = x.as_any_arc();
+        let tid = (*x).type_id();
+
+        let s = match self.entry_or_trace(tid) {
+            Some(s) => s,
+            None => return Err(x),
+        };
+
+        (s.cast_arc)(x)
+    }
+
+    /// Tries to cast the given reference-counted pointer to a dynamic trait
+    /// object. This will always return Err if the implementation of the
+    /// target trait, for the concrete type of x, has not been registered via
+    /// `traitcast!`.
+    ///
+    /// As with `from_arc`, the original pointer is handed back on failure.
+    pub fn from_rc<From>(&self, x: Rc<From>) -> Result<Rc<To>, Rc<dyn Any>>
+    where
+        From: TraitcastFrom + ?Sized,
+    {
+        let x = x.as_any_rc();
+        let tid = (*x).type_id();
+
+        let s = match self.entry_or_trace(tid) {
+            Some(s) => s,
+            None => return Err(x),
+        };
+
+        (s.cast_rc)(x)
+    }
+}
+
+/// A borrowed handle to the table for one trait in a `Registry`, returned by
+/// `Registry::table`.
+pub struct TraitTable<'a, To: ?Sized> {
+    table: &'a CastIntoTrait<To>,
+}
+
+/// Manual `Clone` and `Copy` impls to allow for unsized To.
+impl<'a, To: ?Sized> Clone for TraitTable<'a, To> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, To: ?Sized> Copy for TraitTable<'a, To> {}
+
+impl<'a, To: ?Sized + 'static> TraitTable<'a, To> {
+    /// Gets the table this handle borrows.
+    pub fn get(self) -> &'a CastIntoTrait<To> {
+        self.table
+    }
+
+    /// Like `CastIntoTrait::from_ref`.
+    pub fn from_ref<From>(self, x: &From) -> Option<&To>
+    where
+        From: TraitcastFrom + ?Sized,
+    {
+        self.table.from_ref(x)
+    }
+
+    /// Like `CastIntoTrait::from_mut`.
+    pub fn from_mut<From>(self, x: &mut From) -> Option<&mut To>
+    where
+        From: TraitcastFrom + ?Sized,
+    {
+        self.table.from_mut(x)
+    }
+
+    /// Like `CastIntoTrait::from_box`.
+    pub fn from_box<From>(self, x: Box<From>) -> Result<Box<To>, Box<dyn Any>>
+    where
+        From: TraitcastFrom + ?Sized,
+    {
+        self.table.from_box(x)
+    }
 }
 
 /// An entry in the table for a particular castable trait. Stores methods to
 /// cast into one particular struct that implements the trait.
+#[allow(clippy::type_complexity)]
 pub struct ImplEntry<DynTrait: ?Sized> {
-    pub cast_box: fn(Box<Any>) -> Result<Box<DynTrait>, Box<Any>>,
+    pub cast_box: fn(Box<dyn Any>) -> Result<Box<DynTrait>, Box<dyn Any>>,
+    pub cast_box_send: fn(
+        Box<dyn Any + Send + Sync>,
+    )
+        -> Result<Box<DynTrait>, Box<dyn Any + Send + Sync>>,
+    pub cast_arc: fn(
+        Arc<dyn Any + Send + Sync>,
+    ) -> Result<Arc<DynTrait>, Arc<dyn Any + Send + Sync>>,
+    pub cast_rc: fn(Rc<dyn Any>) -> Result<Rc<DynTrait>, Rc<dyn Any>>,
     pub cast_mut: fn(&mut dyn Any) -> Option<&mut DynTrait>,
     pub cast_ref: fn(&dyn Any) -> Option<&DynTrait>,
     pub tid: TypeId,
     pub from_name: &'static str,
-    pub into_name: &'static str
+    pub into_name: &'static str,
+    /// How to serialize values of the concrete type. Empty without the
+    /// `serde` feature; see the `tagged` module.
+    pub serde: SerdeFns,
+}
+
+#[cfg(feature = "serde")]
+pub use tagged::SerdeFns;
+
+/// Stands in for the serde functions of an `ImplEntry` without the `serde`
+/// feature.
+#[cfg(not(feature = "serde"))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SerdeFns;
+
+/// Expands to the `SerdeFns` for the given concrete type.
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serde_fns {
+    ($target:ty) => {
+        $crate::SerdeFns
+    };
 }
 
-/// Manual `Clone` impl to allow for unsized T.
+/// Manual `Clone` and `Copy` impls to allow for unsized T. Every field is made
+/// of function pointers, a `TypeId` or a `&'static str`, so copying an entry is
+/// cheap and never allocates.
 impl<T: ?Sized> Clone for ImplEntry<T> {
     fn clone(&self) -> Self {
-        ImplEntry {
-            cast_box: self.cast_box,
-            cast_mut: self.cast_mut,
-            cast_ref: self.cast_ref,
-            tid: self.tid,
-            from_name: self.from_name,
-            into_name: self.into_name
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for ImplEntry<T> {}
+
+/// Entries are equal if they are for the same concrete type and names, and
+/// have identical cast functions. Note that two separate `impl_entry!`
+/// invocations for the same pair are not guaranteed to produce identical
+/// functions.
+impl<T: ?Sized> PartialEq for ImplEntry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.tid == other.tid
+            && self.from_name == other.from_name
+            && self.into_name == other.into_name
+            && self.cast_box as usize == other.cast_box as usize
+            && self.cast_box_send as usize == other.cast_box_send as usize
+            && self.cast_arc as usize == other.cast_arc as usize
+            && self.cast_rc as usize == other.cast_rc as usize
+            && self.cast_mut as usize == other.cast_mut as usize
+            && self.cast_ref as usize == other.cast_ref as usize
+    }
+}
+
+impl<T: ?Sized> Eq for ImplEntry<T> {}
+
+/// Hashes the same fields that `PartialEq` compares, so that function
+/// pointers are hashed by address.
+impl<T: ?Sized> Hash for ImplEntry<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tid.hash(state);
+        self.from_name.hash(state);
+        self.into_name.hash(state);
+        (self.cast_box as usize).hash(state);
+        (self.cast_box_send as usize).hash(state);
+        (self.cast_arc as usize).hash(state);
+        (self.cast_rc as usize).hash(state);
+        (self.cast_mut as usize).hash(state);
+        (self.cast_ref as usize).hash(state);
+    }
+}
+
+/// The reasons a cast can fail.
+///
+/// This only relies on `core::fmt`, so it is available without `std`. The
+/// `std::error::Error` impl requires the `std` feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraitcastError {
+    /// There is no table for the target trait at all.
+    TraitNotRegistered {
+        /// The name of the target trait object type.
+        into_name: &'static str,
+    },
+    /// The target trait has a table, but the concrete type of the value being
+    /// cast is not registered in it.
+    ImplNotRegistered {
+        /// The `TypeId` of the concrete type of the value being cast.
+        from: TypeId,
+        /// The name of the target trait object type.
+        into_name: &'static str,
+    },
+}
+
+/// An alias of `TraitcastError`, which is what the fallible cast functions
+/// are documented in terms of.
+pub type CastError = TraitcastError;
+
+impl core::fmt::Display for TraitcastError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            TraitcastError::TraitNotRegistered { into_name } => {
+                write!(f, "trait '{}' is not registered", into_name)
+            }
+            TraitcastError::ImplNotRegistered { into_name, .. } => write!(
+                f,
+                "no registered impl of trait '{}' for the given concrete type",
+                into_name
+            ),
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for TraitcastError {}
+
+/// A failed cast of a box, which keeps the box so that it can be recovered
+/// with `into_inner`, along with the names of the types involved.
+#[derive(Debug)]
+pub struct BoxCastError {
+    /// The box which failed to cast, erased to `dyn Any`.
+    pub original: Box<dyn Any>,
+    /// The name of the static type of the box's contents, e.g. `dyn Foo` or
+    /// `dyn Any`, as given by `std::any::type_name`.
+    pub from_name: &'static str,
+    /// The name of the target trait object type.
+    pub into_name: &'static str,
+}
+
+impl BoxCastError {
+    /// Recovers the box which failed to cast.
+    pub fn into_inner(self) -> Box<dyn Any> {
+        self.original
+    }
+}
+
+impl core::fmt::Display for BoxCastError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "cannot cast box of '{}' into '{}'",
+            self.from_name, self.into_name
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BoxCastError {}
+
+/// Implemented by `traitcast!(registered struct A: Foo, Bar)` for the struct
+/// it registers, so that the traits a struct was registered for can be
+/// checked statically, e.g. by other macros.
+pub trait TraitcastRegistered {
+    /// The names of the trait object types the struct was registered for, in
+    /// the same form as `ImplEntry::from_name`.
+    const TRAITS: &'static [&'static str];
+}
+
 /// Subtraits of `TraitcastFrom` may be cast into `dyn Any`, and thus may be
 /// cast into any other castable dynamic trait object, too. This is blanket
 /// implemented for all sized types with static lifetimes.
@@ -172,12 +1328,43 @@ pub trait TraitcastFrom {
     /// Cast to a boxed reference to a trait object.
     fn as_any_box(self: Box<Self>) -> Box<dyn Any>;
 
+    /// Cast to a reference-counted pointer to a trait object.
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any>;
+
     /// Get the trait object's dynamic type id.
-    fn type_id(&self) -> std::any::TypeId {
+    fn type_id(&self) -> TypeId {
         self.as_any_ref().type_id()
     }
 }
 
+/// The part of `TraitcastFrom` which is only available for `Send + Sync`
+/// types, because e.g. `Arc<dyn Any + Send + Sync>` can only be shared
+/// between threads if its contents can. This is blanket implemented for all sized `Send + Sync`
+/// types with static lifetimes. To cast from `Arc<dyn Foo>`, make it a
+/// supertrait of `Foo` instead of `TraitcastFrom`. The same goes for
+/// `Box<dyn Foo>` with `cast_box_send`.
+///
+/// Casting from a pointer whose trait only has `TraitcastFrom` is a compile
+/// error, even with the bounds spelled out:
+/// ```compile_fail,E0277
+/// # use std::sync::Arc;
+/// # use traitcast_core::{CastIntoTrait, TraitcastFrom};
+/// trait Foo: TraitcastFrom {}
+/// trait Bar {}
+///
+/// fn cast(table: &CastIntoTrait<dyn Bar>, x: Arc<dyn Foo + Send + Sync>) {
+///     let _ = table.from_arc(x);
+/// }
+/// ```
+pub trait TraitcastFromSend: TraitcastFrom + Send + Sync {
+    /// Cast to a boxed reference to a trait object which keeps the `Send +
+    /// Sync` bounds, so that it can still be sent between threads.
+    fn as_any_box_send(self: Box<Self>) -> Box<dyn Any + Send + Sync>;
+
+    /// Cast to an atomically reference-counted pointer to a trait object.
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
 /// Blanket implementation that automatically implements TraitcastFrom for most
 /// user-defined types.
 impl<T> TraitcastFrom for T
@@ -195,6 +1382,20 @@ where
     fn as_any_box(self: Box<Self>) -> Box<dyn Any> {
         self
     }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+impl<T: Any + Send + Sync> TraitcastFromSend for T {
+    fn as_any_box_send(self: Box<Self>) -> Box<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
 }
 
 impl TraitcastFrom for dyn Any {
@@ -209,6 +1410,142 @@ impl TraitcastFrom for dyn Any {
     fn as_any_box(self: Box<Self>) -> Box<dyn Any> {
         self
     }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+impl TraitcastFrom for dyn Any + Send {
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_any_box(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+impl TraitcastFrom for dyn Any + Send + Sync {
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_any_box(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+impl TraitcastFromSend for dyn Any + Send + Sync {
+    fn as_any_box_send(self: Box<Self>) -> Box<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+/// Adds methods to downcast from any implementor of `TraitcastFrom` to its
+/// concrete type, without going through `as_any_ref` by hand.
+///
+/// Like the methods of `Any`, these are also implemented for
+/// `Box<dyn Foo>` itself, so call them on `*x` rather than on the box.
+/// `downcast_concrete_box` takes the box by value, so it is not affected.
+///
+/// # Example
+/// ```
+/// use traitcast_core::{CastExt, TraitcastFrom};
+///
+/// trait Foo: TraitcastFrom {
+///     fn foo(&self) -> i32;
+/// }
+///
+/// struct A {
+///     x: i32,
+/// }
+///
+/// impl Foo for A {
+///     fn foo(&self) -> i32 {
+///         self.x
+///     }
+/// }
+///
+/// let mut x: Box<dyn Foo> = Box::new(A { x: 7 });
+/// (*x).downcast_concrete_mut::<A>().unwrap().x += 1;
+/// assert_eq!((*x).downcast_concrete_ref::<A>().unwrap().x, 8);
+/// assert!((*x).downcast_concrete_ref::<i32>().is_none());
+///
+/// let a: Box<A> = x.downcast_concrete_box().ok().unwrap();
+/// assert_eq!(a.x, 8);
+/// ```
+pub trait CastExt: TraitcastFrom {
+    /// Downcasts to a reference to the concrete type `T`, if that is the
+    /// concrete type of `self`.
+    fn downcast_concrete_ref<T: 'static>(&self) -> Option<&T> {
+        self.as_any_ref().downcast_ref()
+    }
+
+    /// Downcasts to a mutable reference to the concrete type `T`, if that is
+    /// the concrete type of `self`.
+    fn downcast_concrete_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut()
+    }
+
+    /// Downcasts to a box of the concrete type `T`, if that is the concrete
+    /// type of `self`. On failure the box is returned as `Box<dyn Any>`.
+    fn downcast_concrete_box<T: 'static>(
+        self: Box<Self>,
+    ) -> Result<Box<T>, Box<dyn Any>> {
+        self.as_any_box().downcast()
+    }
+}
+
+impl<T: TraitcastFrom + ?Sized> CastExt for T {}
+
+/// Downcasts a `Box<dyn Any + Send + Sync>` to a concrete type, without
+/// requiring `T: Send + Sync`, for the same reasons as `downcast_arc`. This is
+/// an implementation detail of `impl_entry!`.
+#[doc(hidden)]
+pub fn downcast_box_send<T: Any>(
+    x: Box<dyn Any + Send + Sync>,
+) -> Result<Box<T>, Box<dyn Any + Send + Sync>> {
+    x.downcast()
+}
+
+/// Downcasts an `Arc<dyn Any + Send + Sync>` to a concrete type. Unlike
+/// `Arc::downcast`, this does not require `T: Send + Sync`, so that
+/// `impl_entry!` works for every type. This is sound because the `Arc` can
+/// only contain a `T` if `T` is `Send + Sync` in the first place. This is an
+/// implementation detail of `impl_entry!`.
+#[doc(hidden)]
+pub fn downcast_arc<T: Any>(
+    x: Arc<dyn Any + Send + Sync>,
+) -> Result<Arc<T>, Arc<dyn Any + Send + Sync>> {
+    if (*x).is::<T>() {
+        let x = Arc::into_raw(x) as *const T;
+        // Safety: we just checked that the pointee is a `T`, so this is the
+        // same pointer `Arc::<T>::into_raw` would have returned.
+        Ok(unsafe { Arc::from_raw(x) })
+    } else {
+        Err(x)
+    }
 }
 
 /// Constructs a `ImplEntry` for a trait and a concrete struct implementing
@@ -226,8 +1563,25 @@ macro_rules! impl_entry {
     ($source:ty, $target:ty) => {
         $crate::ImplEntry::<$source> {
             cast_box: |x| {
-                let x: Box<$target> = x.downcast()?;
-                let x: Box<$source> = x;
+                let x: $crate::__private::Box<$target> = x.downcast()?;
+                let x: $crate::__private::Box<$source> = x;
+                Ok(x)
+            },
+            cast_box_send: |x| {
+                let x: $crate::__private::Box<$target> =
+                    $crate::downcast_box_send(x)?;
+                let x: $crate::__private::Box<$source> = x;
+                Ok(x)
+            },
+            cast_arc: |x| {
+                let x: $crate::__private::Arc<$target> =
+                    $crate::downcast_arc(x)?;
+                let x: $crate::__private::Arc<$source> = x;
+                Ok(x)
+            },
+            cast_rc: |x| {
+                let x: $crate::__private::Rc<$target> = x.downcast()?;
+                let x: $crate::__private::Rc<$source> = x;
                 Ok(x)
             },
             cast_mut: |x| {
@@ -240,30 +1594,32 @@ macro_rules! impl_entry {
                 let x: &$source = x;
                 Some(x)
             },
-            tid: std::any::TypeId::of::<$target>(),
+            tid: ::core::any::TypeId::of::<$target>(),
             from_name: stringify!($source),
-            into_name: stringify!($target)
+            into_name: stringify!($target),
+            serde: $crate::__serde_fns!($target),
         }
     };
 }
 
 /// Creates a struct named `$wrapper` which wraps `ImplEntry<dyn $trait>` for
 /// the given `$trait`. This is useful because it allows implementing traits on
-/// the `ImplEntry<dyn $trait>` from external modules. This is an
-/// implementation detail of `traitcast_to_trait!`.
+/// the `ImplEntry<dyn $trait>` from external modules. The wrapper can be
+/// collected with `inventory` and passed to
+/// `EntryBuilder::collecting_entries`.
 #[macro_export]
 macro_rules! defn_impl_entry_wrapper {
     ($type:ty, $vis:vis $wrapper:ident) => {
         #[allow(non_camel_case_types)]
         $vis struct $wrapper(pub $crate::ImplEntry<$type>);
 
-        impl std::convert::From<$crate::ImplEntry<$type>> for $wrapper {
+        impl ::core::convert::From<$crate::ImplEntry<$type>> for $wrapper {
             fn from(x: $crate::ImplEntry<$type>) -> Self {
                 $wrapper(x)
             }
         }
 
-        impl std::convert::AsRef<$crate::ImplEntry<$type>> for $wrapper {
+        impl ::core::convert::AsRef<$crate::ImplEntry<$type>> for $wrapper {
             fn as_ref(&self) -> &$crate::ImplEntry<$type> {
                 &self.0
             }
@@ -271,3 +1627,43 @@ macro_rules! defn_impl_entry_wrapper {
     };
 }
 
+/// Registers impls of common std traits in a registry, such as one made by
+/// `Registry::with_std_traits`.
+///
+/// `register_std_traits!(registry; A, B)` registers the `Display` and
+/// `Debug` impls of `A` and `B`. `register_std_traits!(registry; Debug: A)`
+/// registers only the `Debug` impl, for types which have no `Display` impl,
+/// and likewise for `Display: A`.
+///
+/// # Example
+/// ```
+/// # use traitcast_core::{register_std_traits, Registry};
+/// use std::any::Any;
+/// use std::fmt::Display;
+///
+/// let mut registry = Registry::with_std_traits();
+/// register_std_traits!(registry; i32, String);
+///
+/// let x: &dyn Any = &7;
+/// let x: &dyn Display = registry.try_cast_ref(x).unwrap();
+/// assert_eq!(x.to_string(), "7");
+/// ```
+#[macro_export]
+macro_rules! register_std_traits {
+    ($registry:expr; Display: $($type:ty),+ $(,)?) => {
+        $(
+            $registry.register_impl(
+                $crate::impl_entry!(dyn core::fmt::Display, $type));
+        )+
+    };
+    ($registry:expr; Debug: $($type:ty),+ $(,)?) => {
+        $(
+            $registry.register_impl(
+                $crate::impl_entry!(dyn core::fmt::Debug, $type));
+        )+
+    };
+    ($registry:expr; $($type:ty),+ $(,)?) => {
+        $crate::register_std_traits!($registry; Display: $($type),+);
+        $crate::register_std_traits!($registry; Debug: $($type),+);
+    };
+}
@@ -0,0 +1,88 @@
+/*!
+This module builds `ImplEntry`s without `impl_entry!`, using the unstable
+`Unsize` trait to express the coercion from a concrete type into a trait
+object generically. This lets impls be registered from generic code, e.g. in
+a loop over a list of types. Requires the `nightly` feature, and a nightly
+compiler.
+*/
+use core::any::{Any, TypeId};
+use core::marker::Unsize;
+
+use crate::prelude::*;
+use crate::{downcast_arc, downcast_box_send, ImplEntry, Registry};
+
+impl<DynTrait: ?Sized + 'static> ImplEntry<DynTrait> {
+    /// Makes the entry for casting from the concrete type `S` into
+    /// `DynTrait`, like `impl_entry!(DynTrait, S)`.
+    ///
+    /// The names are the full paths given by `core::any::type_name`, rather
+    /// than the types as written. The entry has no serde functions, because
+    /// the `Serialize` impl of a generic `S` can't be detected.
+    pub fn of<S>() -> Self
+    where
+        S: Unsize<DynTrait> + 'static,
+    {
+        ImplEntry {
+            cast_box: |x| {
+                let x: Box<S> = x.downcast()?;
+                Ok(x)
+            },
+            cast_box_send: |x| {
+                let x: Box<S> = downcast_box_send(x)?;
+                Ok(x)
+            },
+            cast_arc: |x| {
+                let x: Arc<S> = downcast_arc(x)?;
+                Ok(x)
+            },
+            cast_rc: |x| {
+                let x: Rc<S> = x.downcast()?;
+                Ok(x)
+            },
+            cast_mut: |x: &mut dyn Any| {
+                let x: &mut S = x.downcast_mut()?;
+                Some(x)
+            },
+            cast_ref: |x: &dyn Any| {
+                let x: &S = x.downcast_ref()?;
+                Some(x)
+            },
+            tid: TypeId::of::<S>(),
+            from_name: core::any::type_name::<DynTrait>(),
+            into_name: core::any::type_name::<S>(),
+            // Always empty, since `S` has no `Serialize` bound here
+            serde: crate::__serde_fns!(S),
+        }
+    }
+}
+
+impl Registry {
+    /// Registers the impl of `DynTrait` for the concrete type `S`, like
+    /// `register_impl(impl_entry!(DynTrait, S))`, and returns whether it
+    /// replaced an existing entry.
+    ///
+    /// # Example
+    /// ```
+    /// # #![feature(unsize)]
+    /// # use traitcast_core::Registry;
+    /// trait Shape: traitcast_core::TraitcastFrom {}
+    ///
+    /// struct Square;
+    /// impl Shape for Square {}
+    ///
+    /// fn register<S: Shape + 'static>(registry: &mut Registry) {
+    ///     registry.register_impl_generic::<S, dyn Shape>();
+    /// }
+    ///
+    /// let mut registry = Registry::new();
+    /// register::<Square>(&mut registry);
+    /// assert!(registry.try_cast_ref::<_, dyn Shape>(&Square).is_ok());
+    /// ```
+    pub fn register_impl_generic<S, DynTrait>(&mut self) -> bool
+    where
+        S: Unsize<DynTrait> + 'static,
+        DynTrait: ?Sized + 'static,
+    {
+        self.register_impl(ImplEntry::<DynTrait>::of::<S>())
+    }
+}
@@ -0,0 +1,125 @@
+/*!
+This module defines `OpcodeTable`, which maps small integer opcodes to casts,
+as used by interpreters that dispatch on opcodes. Casting through it is an
+index into a `Vec` rather than a hash lookup, so the opcode space should be
+dense.
+*/
+use core::any::Any;
+
+use crate::prelude::*;
+use crate::{ImplEntry, TraitcastFrom};
+
+/// A table of casts indexed by opcode. Each opcode has at most one entry,
+/// which casts from one concrete type into one trait.
+///
+/// # Example
+/// ```
+/// # use traitcast_core::opcode::OpcodeTable;
+/// # use traitcast_core::impl_entry;
+/// # use std::any::Any;
+/// trait Op: traitcast_core::TraitcastFrom {
+///     fn run(&self, acc: i64) -> i64;
+/// }
+///
+/// struct Add(i64);
+/// impl Op for Add {
+///     fn run(&self, acc: i64) -> i64 {
+///         acc + self.0
+///     }
+/// }
+///
+/// const ADD: u16 = 0;
+///
+/// let mut table = OpcodeTable::new();
+/// table.register(ADD, impl_entry!(dyn Op, Add));
+///
+/// let x: Box<dyn Any> = Box::new(Add(2));
+/// let op = table.cast_ref::<dyn Op, _>(ADD, &*x).unwrap();
+/// assert_eq!(op.run(1), 3);
+/// ```
+pub struct OpcodeTable {
+    slots: Vec<Option<Slot>>,
+}
+
+/// A type-erased `ImplEntry<To>`.
+type Slot = Box<dyn Any + Send + Sync>;
+
+impl OpcodeTable {
+    /// Makes a new table with no opcodes registered.
+    pub fn new() -> Self {
+        OpcodeTable { slots: Vec::new() }
+    }
+
+    /// Registers the given entry for an opcode, replacing any previous entry
+    /// for it. The table grows to fit the opcode.
+    pub fn register<To>(&mut self, opcode: u16, entry: ImplEntry<To>)
+    where
+        To: ?Sized + 'static,
+    {
+        let idx = usize::from(opcode);
+        if idx >= self.slots.len() {
+            self.slots.resize_with(idx + 1, || None);
+        }
+        self.slots[idx] = Some(Box::new(entry));
+    }
+
+    /// Removes the entry for an opcode. Returns whether there was one.
+    pub fn unregister(&mut self, opcode: u16) -> bool {
+        self.slots
+            .get_mut(usize::from(opcode))
+            .and_then(Option::take)
+            .is_some()
+    }
+
+    /// Whether the opcode has an entry.
+    pub fn contains(&self, opcode: u16) -> bool {
+        self.slot(opcode).is_some()
+    }
+
+    /// Casts `x` with the entry for an opcode. Returns `None` if the opcode
+    /// has no entry, if `To` is not the trait of that entry, or if the
+    /// concrete type of `x` is not its concrete type.
+    pub fn cast_ref<'a, To, From>(
+        &self,
+        opcode: u16,
+        x: &'a From,
+    ) -> Option<&'a To>
+    where
+        To: ?Sized + 'static,
+        From: TraitcastFrom + ?Sized,
+    {
+        let entry = self.entry::<To>(opcode)?;
+        (entry.cast_ref)(x.as_any_ref())
+    }
+
+    /// Like `cast_ref`, for mutable references.
+    pub fn cast_mut<'a, To, From>(
+        &self,
+        opcode: u16,
+        x: &'a mut From,
+    ) -> Option<&'a mut To>
+    where
+        To: ?Sized + 'static,
+        From: TraitcastFrom + ?Sized,
+    {
+        let entry = self.entry::<To>(opcode)?;
+        (entry.cast_mut)(x.as_any_mut())
+    }
+
+    fn slot(&self, opcode: u16) -> Option<&Slot> {
+        self.slots.get(usize::from(opcode))?.as_ref()
+    }
+
+    fn entry<To>(&self, opcode: u16) -> Option<&ImplEntry<To>>
+    where
+        To: ?Sized + 'static,
+    {
+        self.slot(opcode)?.downcast_ref::<ImplEntry<To>>()
+    }
+}
+
+impl Default for OpcodeTable {
+    fn default() -> Self {
+        OpcodeTable::new()
+    }
+}
@@ -0,0 +1,138 @@
+/*!
+This module defines `RegistrySchema`, a description of the casts a `Registry`
+supports which leaves out the cast functions themselves. With the `serde`
+feature it can be serialized, e.g. to diff the casts supported by two builds
+of a binary, or rendered with `Registry::to_mermaid`.
+*/
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::any::TypeId;
+use core::fmt::Write;
+use core::hash::{Hash, Hasher};
+
+use crate::prelude::*;
+use crate::{CastIntoTrait, Registry};
+
+/// Describes a single entry of a table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EntrySchema {
+    /// The name of the trait object type, as given to `impl_entry!`.
+    pub from_name: &'static str,
+    /// The name of the concrete type, as given to `impl_entry!`.
+    pub into_name: &'static str,
+    /// A hash of the `TypeId` of the concrete type. `TypeId`s are only stable
+    /// within a single build, so compare names across builds instead.
+    pub tid: u64,
+}
+
+/// Describes every entry of a registry, sorted by trait name and then by
+/// concrete type name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RegistrySchema {
+    /// One entry per cast the registry supports, across all of its tables.
+    pub entries: Vec<EntrySchema>,
+}
+
+impl<DynTrait: ?Sized, S> CastIntoTrait<DynTrait, S> {
+    /// Describes every entry of the table, sorted by concrete type name.
+    pub fn schema(&self) -> Vec<EntrySchema> {
+        let mut entries: Vec<EntrySchema> = self
+            .map
+            .values()
+            .map(|entry| EntrySchema {
+                from_name: entry.from_name,
+                into_name: entry.into_name,
+                tid: hash_tid(entry.tid),
+            })
+            .collect();
+        entries.sort_by_key(|e| (e.from_name, e.into_name, e.tid));
+        entries
+    }
+}
+
+impl Registry {
+    /// Describes every entry of every table in the registry.
+    pub fn schema(&self) -> RegistrySchema {
+        let mut entries: Vec<EntrySchema> = self
+            .trait_metas()
+            .flat_map(|meta| (meta.schema)(self))
+            .collect();
+        entries.sort_by_key(|e| (e.from_name, e.into_name, e.tid));
+        RegistrySchema { entries }
+    }
+
+    /// Renders the casts the registry supports as a Mermaid flowchart, with
+    /// an edge from each concrete type to each trait it can be cast into.
+    /// Nodes are labelled with the names given to `impl_entry!`.
+    pub fn to_mermaid(&self) -> String {
+        let schema = self.schema();
+
+        // Names aren't valid node ids in general, so number them instead
+        let names: BTreeSet<&'static str> = schema
+            .entries
+            .iter()
+            .flat_map(|e| vec![e.into_name, e.from_name])
+            .collect();
+        let ids: BTreeMap<&'static str, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(i, &name)| (name, i))
+            .collect();
+
+        let mut out = String::from("graph LR\n");
+        for (name, id) in &ids {
+            writeln!(out, "    n{}[\"{}\"]", id, escape(name)).unwrap();
+        }
+        for e in &schema.entries {
+            writeln!(
+                out,
+                "    n{} --> n{}",
+                ids[e.into_name], ids[e.from_name]
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+/// Escapes a name for use in a quoted Mermaid label.
+fn escape(name: &str) -> String {
+    name.replace('"', "#quot;")
+}
+
+fn hash_tid(tid: TypeId) -> u64 {
+    let mut hasher = TidHasher::default();
+    tid.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The standard library's `DefaultHasher`, which is deterministic.
+#[cfg(feature = "std")]
+type TidHasher = std::collections::hash_map::DefaultHasher;
+
+/// FNV-1a, since `DefaultHasher` needs `std`. Only used for `hash_tid`, which
+/// must give the same hash for the same `TypeId` every time.
+#[cfg(not(feature = "std"))]
+struct TidHasher(u64);
+
+#[cfg(not(feature = "std"))]
+impl Default for TidHasher {
+    fn default() -> Self {
+        TidHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Hasher for TidHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
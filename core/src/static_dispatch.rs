@@ -0,0 +1,144 @@
+/*!
+This module defines `StaticDispatch`, a flat table of casts for a fixed set of
+(source type, target trait) pairs, which are resolved against a registry once,
+ahead of time. Casting through it is an index into a slice rather than a hash
+lookup, which is useful on latency-critical paths where the set of types is
+known up front.
+*/
+use core::any::{Any, TypeId};
+
+use crate::prelude::*;
+use crate::{Registry, TraitcastError, TraitcastFrom};
+
+/// Collects the (source type, target trait) pairs for a `StaticDispatch`.
+/// Each call to `add` appends a slot, so slots are numbered in the order they
+/// were added.
+pub struct StaticDispatchBuilder {
+    resolvers: Vec<Resolver>,
+}
+
+/// Looks up the slot for one pair in a registry.
+type Resolver = fn(&Registry) -> Result<Slot, TraitcastError>;
+
+/// A type-erased `fn(&dyn Any) -> Option<&To>`, taken directly from the
+/// `ImplEntry` for a pair.
+type Slot = Box<dyn Any + Send + Sync>;
+
+/// A precomputed table of casts. See the module documentation.
+///
+/// # Example
+/// ```
+/// # use traitcast_core::static_dispatch::StaticDispatch;
+/// # use traitcast_core::{impl_entry, Registry};
+/// # use std::any::Any;
+/// trait Shape: traitcast_core::TraitcastFrom {
+///     fn area(&self) -> f64;
+/// }
+///
+/// struct Square(f64);
+/// impl Shape for Square {
+///     fn area(&self) -> f64 {
+///         self.0 * self.0
+///     }
+/// }
+///
+/// enum Kind {
+///     Square,
+/// }
+///
+/// let mut registry = Registry::new();
+/// registry.insert::<dyn Shape>(
+///     vec![impl_entry!(dyn Shape, Square)].into_iter().collect(),
+/// );
+///
+/// let dispatch = StaticDispatch::builder()
+///     .add::<Square, dyn Shape>()
+///     .build(&registry)
+///     .unwrap();
+///
+/// let x: Box<dyn Any> = Box::new(Square(2.0));
+/// let shape = dispatch.dispatch::<dyn Shape, _>(Kind::Square as usize, &*x);
+/// assert_eq!(shape.unwrap().area(), 4.0);
+/// ```
+pub struct StaticDispatch {
+    slots: Vec<Slot>,
+}
+
+impl StaticDispatch {
+    /// Starts building a dispatch table with no slots.
+    pub fn builder() -> StaticDispatchBuilder {
+        StaticDispatchBuilder {
+            resolvers: Vec::new(),
+        }
+    }
+
+    /// The number of slots in the table.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Whether the table has no slots.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Casts `x` with the slot at `idx`. Returns `None` if `To` is not the
+    /// target trait of that slot, or if the concrete type of `x` is not its
+    /// source type.
+    ///
+    /// # Panics
+    /// Panics if `idx` is out of bounds.
+    pub fn dispatch<'a, To, From>(
+        &self,
+        idx: usize,
+        x: &'a From,
+    ) -> Option<&'a To>
+    where
+        To: ?Sized + 'static,
+        From: TraitcastFrom + ?Sized,
+    {
+        let cast_ref =
+            self.slots[idx].downcast_ref::<fn(&dyn Any) -> Option<&To>>()?;
+        cast_ref(x.as_any_ref())
+    }
+}
+
+impl StaticDispatchBuilder {
+    /// Appends a slot casting from the concrete type `S` into `To`.
+    pub fn add<S, To>(mut self) -> Self
+    where
+        S: Any,
+        To: ?Sized + 'static,
+    {
+        self.resolvers.push(resolve::<S, To>);
+        self
+    }
+
+    /// Resolves every slot against the given registry. Fails if any of the
+    /// pairs is not registered.
+    pub fn build(
+        self,
+        registry: &Registry,
+    ) -> Result<StaticDispatch, TraitcastError> {
+        let slots = self
+            .resolvers
+            .into_iter()
+            .map(|resolve| resolve(registry))
+            .collect::<Result<_, _>>()?;
+        Ok(StaticDispatch { slots })
+    }
+}
+
+fn resolve<S, To>(registry: &Registry) -> Result<Slot, TraitcastError>
+where
+    S: Any,
+    To: ?Sized + 'static,
+{
+    let into_name = core::any::type_name::<To>();
+    let table = registry.cast_into_checked::<To>()?;
+    let from = TypeId::of::<S>();
+    let entry = table
+        .get(from)
+        .ok_or(TraitcastError::ImplNotRegistered { from, into_name })?;
+    Ok(Box::new(entry.cast_ref))
+}
@@ -0,0 +1,302 @@
+/*!
+This module lets a `Registry` serialize trait objects, tagged with the name of
+their concrete type, and deserialize them again by that tag, which makes the
+registry a type-tag table for persisting heterogeneous collections such as
+`Vec<Box<dyn Component>>`. Requires the `serde` feature.
+
+`impl_entry!` fills in `SerdeFns` for concrete types which implement
+`Serialize` or `DeserializeOwned`, and leaves them empty for other types, so
+registering a type which can't be serialized is not an error until one of its
+values is serialized.
+*/
+use std::any::Any;
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::{ImplEntry, Registry, TraitcastFrom};
+
+/// Gives a type-erased view of a value, which must have the concrete type the
+/// function was made for, as `erased_serde::Serialize`.
+pub type SerializeFn =
+    for<'a> fn(&'a dyn Any) -> &'a dyn erased_serde::Serialize;
+
+/// Deserializes a value of the concrete type the function was made for.
+pub type DeserializeFn =
+    for<'de> fn(
+        &mut dyn erased_serde::Deserializer<'de>,
+    ) -> Result<Box<dyn Any>, erased_serde::Error>;
+
+/// The serde functions of an `ImplEntry`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SerdeFns {
+    /// Serializes values of the concrete type, if it implements `Serialize`.
+    pub serialize: Option<SerializeFn>,
+    /// Deserializes values of the concrete type, if it implements
+    /// `DeserializeOwned`. The result is cast with the entry's `cast_box`.
+    pub deserialize: Option<DeserializeFn>,
+}
+
+fn serialize_any<T: serde::Serialize + 'static>(
+    x: &dyn Any,
+) -> &dyn erased_serde::Serialize {
+    x.downcast_ref::<T>()
+        .expect("SerdeFns used with a value of the wrong concrete type")
+}
+
+fn deserialize_any<T: serde::de::DeserializeOwned + 'static>(
+    deserializer: &mut dyn erased_serde::Deserializer,
+) -> Result<Box<dyn Any>, erased_serde::Error> {
+    let x: T = erased_serde::deserialize(deserializer)?;
+    Ok(Box::new(x))
+}
+
+/// Selects the `SerdeFns` for `T` by autoref specialization, which works in
+/// `impl_entry!` because the concrete type is known where it is expanded.
+/// This is an implementation detail of `impl_entry!`.
+#[doc(hidden)]
+pub struct Probe<T>(pub PhantomData<T>);
+
+#[doc(hidden)]
+pub trait ViaSerialize {
+    fn serialize_fn(&self) -> Option<SerializeFn>;
+}
+
+impl<T: serde::Serialize + 'static> ViaSerialize for Probe<T> {
+    fn serialize_fn(&self) -> Option<SerializeFn> {
+        Some(serialize_any::<T>)
+    }
+}
+
+#[doc(hidden)]
+pub trait ViaDeserialize {
+    fn deserialize_fn(&self) -> Option<DeserializeFn>;
+}
+
+impl<T: serde::de::DeserializeOwned + 'static> ViaDeserialize for Probe<T> {
+    fn deserialize_fn(&self) -> Option<DeserializeFn> {
+        Some(deserialize_any::<T>)
+    }
+}
+
+#[doc(hidden)]
+pub trait ViaDefault {
+    fn serialize_fn(&self) -> Option<SerializeFn> {
+        None
+    }
+
+    fn deserialize_fn(&self) -> Option<DeserializeFn> {
+        None
+    }
+}
+
+impl<T> ViaDefault for &Probe<T> {}
+
+/// Expands to the `SerdeFns` for the given concrete type.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __serde_fns {
+    ($target:ty) => {{
+        #[allow(unused_imports)]
+        use $crate::tagged::{
+            ViaDefault as _, ViaDeserialize as _, ViaSerialize as _,
+        };
+        let probe =
+            &$crate::tagged::Probe::<$target>($crate::__private::PhantomData);
+        $crate::tagged::SerdeFns {
+            serialize: probe.serialize_fn(),
+            deserialize: probe.deserialize_fn(),
+        }
+    }};
+}
+
+impl Registry {
+    /// Serializes the given trait object as a map with a single entry, from
+    /// the name of its concrete type, as given to `impl_entry!`, to the value
+    /// itself. This is the same shape as an externally tagged enum.
+    ///
+    /// Fails if the concrete type is not registered for `To`, or does not
+    /// implement `Serialize`.
+    ///
+    /// # Example
+    /// ```
+    /// # use traitcast_core::{impl_entry, Registry};
+    /// # use serde::Serialize;
+    /// trait Component: traitcast_core::TraitcastFrom {}
+    ///
+    /// #[derive(Serialize)]
+    /// struct Position {
+    ///     x: i32,
+    /// }
+    /// impl Component for Position {}
+    ///
+    /// let mut registry = Registry::new();
+    /// registry.register_impl(impl_entry!(dyn Component, Position));
+    ///
+    /// let x: Box<dyn Component> = Box::new(Position { x: 1 });
+    /// let mut out = Vec::new();
+    /// registry
+    ///     .serialize_trait_object(
+    ///         &*x,
+    ///         &mut serde_json::Serializer::new(&mut out),
+    ///     )
+    ///     .unwrap();
+    /// assert_eq!(out, br#"{"Position":{"x":1}}"#);
+    /// ```
+    pub fn serialize_trait_object<To, S>(
+        &self,
+        x: &To,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        To: TraitcastFrom + ?Sized + 'static,
+        S: serde::Serializer,
+    {
+        use serde::ser::{Error, SerializeMap};
+
+        let x = x.as_any_ref();
+        let entry = self
+            .cast_into::<To>()
+            .and_then(|table| table.get((*x).type_id()))
+            .ok_or_else(|| {
+                S::Error::custom(format!(
+                    "the concrete type is not registered for '{}'",
+                    std::any::type_name::<To>()
+                ))
+            })?;
+        let serialize = entry.serde.serialize.ok_or_else(|| {
+            S::Error::custom(format!(
+                "'{}' does not implement Serialize",
+                entry.into_name
+            ))
+        })?;
+
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(entry.into_name, serialize(x))?;
+        map.end()
+    }
+
+    /// Deserializes a trait object whose concrete type has the given name, as
+    /// given to `impl_entry!`, from the deserializer. This is the counterpart
+    /// of the value in the map written by `serialize_trait_object`, for
+    /// formats or containers which store the tag separately.
+    ///
+    /// Fails if no concrete type with that name is registered for `To`, or if
+    /// it does not implement `DeserializeOwned`.
+    pub fn deserialize_trait_object<'de, To, D>(
+        &self,
+        tag: &str,
+        deserializer: D,
+    ) -> Result<Box<To>, D::Error>
+    where
+        To: ?Sized + 'static,
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let entry = self.entry_by_tag::<To>(tag).ok_or_else(|| {
+            D::Error::custom(format!(
+                "no type named '{}' is registered for '{}'",
+                tag,
+                std::any::type_name::<To>()
+            ))
+        })?;
+        let deserialize = entry.serde.deserialize.ok_or_else(|| {
+            D::Error::custom(format!(
+                "'{}' does not implement Deserialize",
+                entry.into_name
+            ))
+        })?;
+
+        let mut erased = <dyn erased_serde::Deserializer>::erase(deserializer);
+        let x = deserialize(&mut erased).map_err(D::Error::custom)?;
+        Ok((entry.cast_box)(x)
+            .expect("deserialized a value of the wrong concrete type"))
+    }
+
+    /// Deserializes a trait object in the tagged form written by
+    /// `serialize_trait_object`, i.e. a map with a single entry from the name
+    /// of its concrete type to the value.
+    pub fn deserialize_tagged_trait_object<'de, To, D>(
+        &self,
+        deserializer: D,
+    ) -> Result<Box<To>, D::Error>
+    where
+        To: ?Sized + 'static,
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(TaggedVisitor::<To> {
+            registry: self,
+            marker: PhantomData,
+        })
+    }
+
+    /// Finds the entry for the concrete type with the given name in the
+    /// table for `To`.
+    fn entry_by_tag<To: ?Sized + 'static>(
+        &self,
+        tag: &str,
+    ) -> Option<&ImplEntry<To>> {
+        self.cast_into::<To>()?.get_by_name(tag)
+    }
+}
+
+/// Reads the single entry of the map written by `serialize_trait_object`.
+struct TaggedVisitor<'a, To: ?Sized> {
+    registry: &'a Registry,
+    marker: PhantomData<fn() -> Box<To>>,
+}
+
+impl<'de, 'a, To: ?Sized + 'static> serde::de::Visitor<'de>
+    for TaggedVisitor<'a, To>
+{
+    type Value = Box<To>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a map from a type name to a '{}'",
+            std::any::type_name::<To>()
+        )
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Box<To>, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        use serde::de::Error;
+
+        let tag: String = map
+            .next_key()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let x = map.next_value_seed(TagSeed::<To> {
+            registry: self.registry,
+            tag: &tag,
+            marker: PhantomData,
+        })?;
+        if map.next_key::<serde::de::IgnoredAny>()?.is_some() {
+            return Err(A::Error::invalid_length(2, &self));
+        }
+        Ok(x)
+    }
+}
+
+/// Deserializes the value of a trait object whose tag has been read already.
+struct TagSeed<'a, To: ?Sized> {
+    registry: &'a Registry,
+    tag: &'a str,
+    marker: PhantomData<fn() -> Box<To>>,
+}
+
+impl<'de, 'a, To: ?Sized + 'static> serde::de::DeserializeSeed<'de>
+    for TagSeed<'a, To>
+{
+    type Value = Box<To>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Box<To>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        self.registry
+            .deserialize_trait_object(self.tag, deserializer)
+    }
+}
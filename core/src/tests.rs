@@ -1,119 +1,1693 @@
-// #![cfg(test)]
-
-// use std::any::Any;
-
-// mod traits {
-//     pub trait Foo: crate::TraitcastFrom {
-//         fn foo(&mut self) -> i64;
-//     }
-
-//     pub trait Bar: crate::TraitcastFrom {
-//         fn bar(&self) -> i64;
-//     }
-
-//     pub trait Baz: crate::TraitcastFrom {
-//         fn baz(self: Box<Self>) -> i64;
-//     }
-
-//     crate::traitcast_to_trait!(Foo, Foo_Traitcast);
-//     crate::traitcast_to_trait!(Bar, Bar_Traitcast);
-//     crate::traitcast_to_trait!(Baz, Baz_Traitcast);
-// }
-
-// mod structs {
-//     use crate::tests::traits::{Foo, Bar, Baz};
-//     pub struct A {
-//         pub x: i64
-//     }
-
-//     pub struct B {
-//         pub y: i64
-//     }
-
-//     impl Foo for A {
-//         fn foo(&mut self) -> i64 {
-//             self.x += 1;
-//             self.x
-//         }
-//     }
-
-//     impl Bar for A {
-//         fn bar(&self) -> i64 {
-//             self.x
-//         }
-//     }
-
-//     impl Foo for B {
-//         fn foo(&mut self) -> i64 {
-//             self.y *= 2;
-//             self.y
-//         }
-//     }
-
-//     impl Baz for B {
-//         fn baz(self: Box<Self>) -> i64 {
-//             self.y
-//         }
-//     }
-
-//     crate::traitcast_to_impl!(Foo, A);
-//     crate::traitcast_to_impl!(Bar, A);
-//     crate::traitcast_to_impl!(Foo, B);
-//     crate::traitcast_to_impl!(Baz, B);
-// }
-
-// use traits::*;
-// use structs::*;
-
-// use crate::Traitcast;
-
-// #[test]
-// fn test_traitcast() {
-//     let mut x: Box<Any> = Box::new(A { x: 0 });
-//     let mut y: Box<Any> = Box::new(B { y: 1 });
-
-//     {
-//         // Can cast from Any to Bar
-//         let x: &dyn Bar = (*x).cast_ref().unwrap();
-//         assert_eq!(x.bar(), 0);
-
-//         // Can cast from Bar to Foo
-//         assert!(crate::cast_ref::<dyn Bar, dyn Foo>(x).is_some());
-
-//         // Any to Bar cast fails when the type does not implement Bar
-//         assert!(crate::cast_ref::<dyn Any, dyn Bar>(&*y).is_none());
-//     }
-
-//     {
-//         // Can cast from Any to Foo
-//         let x: &mut dyn Foo = (*x).cast_mut().unwrap();
-//         assert_eq!(x.foo(), 1);
-//         assert_eq!(x.foo(), 2);
-
-//         // Can cast from Foo to Bar
-//         let x: &mut dyn Bar = x.cast_mut().unwrap();
-//         assert_eq!(x.bar(), 2);
-
-//         // Can also cast B from Any to Foo
-//         let y: &mut dyn Foo = (*y).cast_mut().unwrap();
-//         assert_eq!(y.foo(), 2);
-//         assert_eq!(y.foo(), 4);
-
-//         // Can cast from Foo to Baz
-//         assert!(crate::cast_mut::<dyn Foo, dyn Baz>(y).is_some());
-//     }
-
-//     {
-//         // Any to Baz fails when the type does not implement Baz
-//         assert!(crate::cast_box::<dyn Any, dyn Baz>(x).is_err());
-
-//         let mut y: Box<dyn Baz> = y.cast_box().unwrap();
-//         // Can cast from Baz to Foo
-//         {
-//             let y: &mut dyn Foo = (*y).cast_mut().unwrap();
-//             assert_eq!(y.foo(), 8);
-//         }
-
-//         assert_eq!(y.baz(), 8);
-//     }
-// }
+#![cfg(test)]
+
+use std::any::Any;
+
+use crate::audit::{RegistryAudit, TraitAudit};
+use crate::builder::RegistryBuilder;
+use crate::static_dispatch::StaticDispatch;
+use crate::visitor::TypeVisitor;
+#[cfg(feature = "nightly")]
+use crate::ImplEntry;
+use crate::{impl_entry, CastIntoTrait, Registry, TraitcastError};
+
+mod traits {
+    pub trait Foo: crate::TraitcastFrom {
+        fn foo(&mut self) -> i64;
+    }
+
+    pub trait Bar: crate::TraitcastFrom {
+        fn bar(&self) -> i64;
+    }
+
+    pub trait Baz: crate::TraitcastFrom {
+        fn baz(self: Box<Self>) -> i64;
+    }
+
+    /// Has no impls at all.
+    pub trait Qux: crate::TraitcastFrom {}
+}
+
+mod structs {
+    use crate::tests::traits::{Bar, Baz, Foo};
+    pub struct A {
+        pub x: i64,
+    }
+
+    pub struct B {
+        pub y: i64,
+    }
+
+    impl Foo for A {
+        fn foo(&mut self) -> i64 {
+            self.x += 1;
+            self.x
+        }
+    }
+
+    impl Bar for A {
+        fn bar(&self) -> i64 {
+            self.x
+        }
+    }
+
+    impl Foo for B {
+        fn foo(&mut self) -> i64 {
+            self.y *= 2;
+            self.y
+        }
+    }
+
+    impl Baz for B {
+        fn baz(self: Box<Self>) -> i64 {
+            self.y
+        }
+    }
+}
+
+/// Defines a second struct named `A`, to test name collisions.
+mod other {
+    use crate::tests::traits::Foo;
+    use crate::{impl_entry, ImplEntry};
+
+    pub struct A;
+
+    impl Foo for A {
+        fn foo(&mut self) -> i64 {
+            0
+        }
+    }
+
+    pub fn foo_entry() -> ImplEntry<dyn Foo> {
+        impl_entry!(dyn Foo, A)
+    }
+}
+
+use structs::*;
+use traits::*;
+
+/// Builds a registry containing every impl defined in this module.
+fn make_registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.insert::<dyn Foo>(
+        vec![impl_entry!(dyn Foo, A), impl_entry!(dyn Foo, B)]
+            .into_iter()
+            .collect(),
+    );
+    registry
+        .insert::<dyn Bar>(vec![impl_entry!(dyn Bar, A)].into_iter().collect());
+    registry
+        .insert::<dyn Baz>(vec![impl_entry!(dyn Baz, B)].into_iter().collect());
+    registry
+}
+
+#[test]
+fn test_registry() {
+    let registry = make_registry();
+    let mut x: Box<dyn Any> = Box::new(A { x: 0 });
+    let mut y: Box<dyn Any> = Box::new(B { y: 1 });
+
+    {
+        // Can cast from Any to Bar
+        let x: &dyn Bar = registry
+            .cast_into::<dyn Bar>()
+            .unwrap()
+            .from_ref(&*x)
+            .unwrap();
+        assert_eq!(x.bar(), 0);
+
+        // Can cast from Bar to Foo
+        assert!(registry
+            .cast_into::<dyn Foo>()
+            .unwrap()
+            .from_ref(x)
+            .is_some());
+
+        // Any to Bar cast fails when the type does not implement Bar
+        assert!(registry
+            .cast_into::<dyn Bar>()
+            .unwrap()
+            .from_ref(&*y)
+            .is_none());
+    }
+
+    {
+        // Can cast from Any to Foo
+        let x: &mut dyn Foo = registry
+            .cast_into::<dyn Foo>()
+            .unwrap()
+            .from_mut(&mut *x)
+            .unwrap();
+        assert_eq!(x.foo(), 1);
+        assert_eq!(x.foo(), 2);
+
+        // Can cast from Foo to Bar
+        let x: &mut dyn Bar = registry
+            .cast_into::<dyn Bar>()
+            .unwrap()
+            .from_mut(x)
+            .unwrap();
+        assert_eq!(x.bar(), 2);
+
+        // Can also cast B from Any to Foo
+        let y: &mut dyn Foo = registry
+            .cast_into::<dyn Foo>()
+            .unwrap()
+            .from_mut(&mut *y)
+            .unwrap();
+        assert_eq!(y.foo(), 2);
+        assert_eq!(y.foo(), 4);
+    }
+
+    {
+        // Any to Baz fails when the type does not implement Baz
+        let baz = registry.cast_into::<dyn Baz>().unwrap();
+        assert!(baz.from_box(x).is_err());
+
+        let y: Box<dyn Baz> = baz.from_box(y).ok().unwrap();
+        assert_eq!(y.baz(), 4);
+    }
+}
+
+#[test]
+fn test_cast_into_mut() {
+    let mut registry = Registry::new();
+    assert!(registry.cast_into_mut::<dyn Foo>().is_none());
+
+    registry.insert::<dyn Foo>(CastIntoTrait::new());
+    let entry = impl_entry!(dyn Foo, B);
+    registry
+        .cast_into_mut::<dyn Foo>()
+        .unwrap()
+        .map
+        .insert(entry.tid, entry);
+
+    let mut y = B { y: 3 };
+    let y: &mut dyn Foo = registry
+        .cast_into::<dyn Foo>()
+        .unwrap()
+        .from_mut(&mut y)
+        .unwrap();
+    assert_eq!(y.foo(), 6);
+}
+
+#[test]
+fn test_audit() {
+    let mut registry = make_registry();
+    registry.insert::<dyn Qux>(CastIntoTrait::new());
+    let entry = other::foo_entry();
+    registry
+        .cast_into_mut::<dyn Foo>()
+        .unwrap()
+        .map
+        .insert(entry.tid, entry);
+
+    let bar = "dyn traitcast_core::tests::traits::Bar";
+    let baz = "dyn traitcast_core::tests::traits::Baz";
+    let foo = "dyn traitcast_core::tests::traits::Foo";
+    let qux = "dyn traitcast_core::tests::traits::Qux";
+
+    assert_eq!(
+        registry.audit(),
+        RegistryAudit {
+            trait_count: 4,
+            impl_count: 5,
+            traits: vec![
+                TraitAudit {
+                    name: bar,
+                    impls: vec!["A"],
+                },
+                TraitAudit {
+                    name: baz,
+                    impls: vec!["B"],
+                },
+                TraitAudit {
+                    name: foo,
+                    impls: vec!["A", "A", "B"],
+                },
+                TraitAudit {
+                    name: qux,
+                    impls: vec![],
+                },
+            ],
+            duplicates: vec![(foo, "A")],
+            empty_tables: vec![qux],
+        }
+    );
+}
+
+#[test]
+fn test_extend_table() {
+    let mut table: CastIntoTrait<dyn Foo> =
+        vec![impl_entry!(dyn Foo, A)].into_iter().collect();
+    table.extend(vec![impl_entry!(dyn Foo, B)].into_iter().collect());
+
+    let mut a = A { x: 0 };
+    let mut b = B { y: 1 };
+    assert_eq!(table.from_mut(&mut a).unwrap().foo(), 1);
+    assert_eq!(table.from_mut(&mut b).unwrap().foo(), 2);
+}
+
+#[test]
+fn test_error_display() {
+    use core::fmt::Write;
+
+    /// A fixed-size buffer, so that formatting needs neither `std` nor
+    /// `alloc`.
+    struct Buf {
+        bytes: [u8; 128],
+        len: usize,
+    }
+
+    impl Write for Buf {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            let end = self.len + s.len();
+            self.bytes
+                .get_mut(self.len..end)
+                .ok_or(core::fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    let mut buf = Buf {
+        bytes: [0; 128],
+        len: 0,
+    };
+    let err = TraitcastError::ImplNotRegistered {
+        from: std::any::TypeId::of::<A>(),
+        into_name: "dyn Bar",
+    };
+    write!(buf, "{}", err).unwrap();
+    assert_eq!(
+        core::str::from_utf8(&buf.bytes[..buf.len]).unwrap(),
+        "no registered impl of trait 'dyn Bar' for the given concrete type"
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_std_error() {
+    let err: Box<dyn std::error::Error> =
+        Box::new(TraitcastError::TraitNotRegistered {
+            into_name: "dyn Bar",
+        });
+    assert_eq!(err.to_string(), "trait 'dyn Bar' is not registered");
+}
+
+#[test]
+fn test_type_visitor() {
+    let visitor = TypeVisitor::new()
+        .on(|a: &A| a.x * 10)
+        .on(|b: &B| b.y * 100);
+
+    let a: Box<dyn Any> = Box::new(A { x: 1 });
+    let b: Box<dyn Bar> = Box::new(A { x: 2 });
+    assert_eq!(visitor.visit(&*a), Some(10));
+    assert_eq!(visitor.visit(&*b), Some(20));
+    assert_eq!(visitor.visit(&B { y: 3 }), Some(300));
+
+    // Falls through for types without a handler
+    assert_eq!(visitor.visit(&other::A), None);
+}
+
+#[test]
+fn test_registry_builder() {
+    let registry = RegistryBuilder::new()
+        .trait_entry::<dyn Foo>()
+        .entries(vec![impl_entry!(dyn Foo, A), impl_entry!(dyn Foo, B)])
+        .trait_entry::<dyn Bar>()
+        .entry(impl_entry!(dyn Bar, A))
+        .done()
+        .trait_entry::<dyn Baz>()
+        .build();
+    assert_eq!(registry.impls_for::<dyn Baz>(), Some(vec![]));
+
+    let b = B { y: 1 };
+    assert!(registry.try_cast_ref::<_, dyn Foo>(&b).is_ok());
+    assert!(registry.try_cast_ref::<_, dyn Bar>(&b).is_err());
+
+    // Builds on top of an existing registry
+    let registry = RegistryBuilder::from_registry(registry)
+        .entry(impl_entry!(dyn Baz, B))
+        .build();
+    assert!(registry.is_superset_of(&make_registry()));
+    assert!(make_registry().is_superset_of(&registry));
+}
+
+#[test]
+fn test_cast_into_trait_accessors() {
+    use std::any::TypeId;
+
+    let mut table: CastIntoTrait<dyn Foo> = CastIntoTrait::new();
+    assert!(table.is_empty());
+    assert!(table.insert(impl_entry!(dyn Foo, A)).is_none());
+    assert!(table.insert(impl_entry!(dyn Foo, B)).is_none());
+    let replaced = table.insert(impl_entry!(dyn Foo, A)).unwrap();
+    assert_eq!(replaced.tid, TypeId::of::<A>());
+    assert_eq!(table.len(), 2);
+
+    assert!(table.contains(TypeId::of::<B>()));
+    assert!(!table.contains(TypeId::of::<other::A>()));
+    assert_eq!(table.get(TypeId::of::<A>()).unwrap().tid, TypeId::of::<A>());
+    assert!(table.get(TypeId::of::<other::A>()).is_none());
+
+    let mut tids: Vec<TypeId> = table.iter().map(|entry| entry.tid).collect();
+    tids.sort_unstable();
+    let mut expected = vec![TypeId::of::<A>(), TypeId::of::<B>()];
+    expected.sort_unstable();
+    assert_eq!(tids, expected);
+}
+
+#[test]
+fn test_name_of() {
+    use std::any::TypeId;
+
+    let registry = make_registry();
+    assert_eq!(registry.name_of(TypeId::of::<A>()), Some("A"));
+    assert_eq!(registry.name_of(TypeId::of::<B>()), Some("B"));
+    assert_eq!(registry.name_of(TypeId::of::<other::A>()), None);
+
+    // Traits are not concrete types
+    assert_eq!(registry.name_of(TypeId::of::<dyn Foo>()), None);
+
+    let x: Box<dyn Any> = Box::new(B { y: 1 });
+    assert_eq!(registry.name_of((*x).type_id()), Some("B"));
+}
+
+#[cfg(feature = "nightly")]
+#[test]
+fn test_register_impl_generic() {
+    fn register_foo<T: Foo + 'static>(registry: &mut Registry) -> bool {
+        registry.register_impl_generic::<T, dyn Foo>()
+    }
+
+    let mut registry = Registry::new();
+    assert!(!register_foo::<A>(&mut registry));
+    assert!(!register_foo::<B>(&mut registry));
+    assert!(register_foo::<A>(&mut registry));
+    assert!(!registry.register_impl_generic::<A, dyn Bar>());
+
+    let mut a: Box<dyn Any> = Box::new(A { x: 1 });
+    let foo = registry.cast_into::<dyn Foo>().unwrap();
+    assert_eq!(foo.from_mut(&mut *a).unwrap().foo(), 2);
+    assert_eq!(foo.from_ref(&B { y: 1 }).map(|_| ()), Some(()));
+    let bar: Box<dyn Bar> = registry.try_cast_box(a).unwrap();
+    assert_eq!(bar.bar(), 2);
+    assert!(registry.try_cast_ref::<_, dyn Baz>(&B { y: 1 }).is_err());
+
+    let entry = ImplEntry::<dyn Foo>::of::<B>();
+    assert_eq!(entry.into_name, std::any::type_name::<B>());
+    assert_eq!(entry.from_name, std::any::type_name::<dyn Foo>());
+}
+
+#[test]
+fn test_static_dispatch() {
+    let registry = make_registry();
+    let dispatch = StaticDispatch::builder()
+        .add::<A, dyn Foo>()
+        .add::<B, dyn Foo>()
+        .add::<A, dyn Bar>()
+        .build(&registry)
+        .unwrap();
+    assert_eq!(dispatch.len(), 3);
+
+    let a: Box<dyn Any> = Box::new(A { x: 5 });
+    let b: Box<dyn Any> = Box::new(B { y: 6 });
+    assert!(dispatch.dispatch::<dyn Foo, _>(0, &*a).is_some());
+    assert!(dispatch.dispatch::<dyn Foo, _>(1, &*b).is_some());
+    assert_eq!(dispatch.dispatch::<dyn Bar, _>(2, &*a).unwrap().bar(), 5);
+
+    // Wrong source type for the slot
+    assert!(dispatch.dispatch::<dyn Foo, _>(0, &*b).is_none());
+    // Wrong target trait for the slot
+    assert!(dispatch.dispatch::<dyn Bar, _>(0, &*a).is_none());
+
+    // Building fails for pairs missing from the registry
+    assert_eq!(
+        StaticDispatch::builder()
+            .add::<B, dyn Bar>()
+            .build(&registry)
+            .err(),
+        Some(TraitcastError::ImplNotRegistered {
+            from: std::any::TypeId::of::<B>(),
+            into_name: "dyn traitcast_core::tests::traits::Bar"
+        })
+    );
+    assert_eq!(
+        StaticDispatch::builder()
+            .add::<A, dyn Qux>()
+            .build(&registry)
+            .err(),
+        Some(TraitcastError::TraitNotRegistered {
+            into_name: "dyn traitcast_core::tests::traits::Qux"
+        })
+    );
+}
+
+#[test]
+fn test_default_table() {
+    let mut table: CastIntoTrait<dyn Bar> = Default::default();
+    assert!(table.map.is_empty());
+    assert!(table.from_ref(&A { x: 1 }).is_none());
+
+    let entry = impl_entry!(dyn Bar, A);
+    table.map.insert(entry.tid, entry);
+    assert_eq!(table.from_ref(&A { x: 1 }).unwrap().bar(), 1);
+}
+
+#[test]
+fn test_trait_count_for() {
+    use std::any::TypeId;
+
+    let registry = make_registry();
+    assert_eq!(registry.trait_count_for(TypeId::of::<A>()), 2);
+    assert_eq!(registry.trait_count_for(TypeId::of::<B>()), 2);
+    assert_eq!(registry.trait_count_for(TypeId::of::<other::A>()), 0);
+}
+
+#[test]
+fn test_cast_into_checked() {
+    use crate::CastError;
+
+    let registry = make_registry();
+    assert!(registry.cast_into_checked::<dyn Bar>().is_ok());
+    assert_eq!(
+        registry.cast_into_checked::<dyn Qux>().err(),
+        Some(CastError::TraitNotRegistered {
+            into_name: "dyn traitcast_core::tests::traits::Qux"
+        })
+    );
+}
+
+#[test]
+fn test_table() {
+    let registry = make_registry();
+    assert!(registry.table::<dyn Qux>().is_none());
+
+    // One handle serves every cast, and can be copied freely
+    let foo = registry.table::<dyn Foo>().unwrap();
+    let copy = foo;
+    let mut xs: Vec<Box<dyn Any>> =
+        vec![Box::new(A { x: 1 }), Box::new(B { y: 3 }), Box::new(7)];
+    let sums: Vec<_> = xs
+        .iter_mut()
+        .map(|x| copy.from_mut(&mut **x).map(|x| x.foo()))
+        .collect();
+    assert_eq!(sums, vec![Some(2), Some(6), None]);
+
+    let bar = registry.table::<dyn Bar>().unwrap();
+    assert_eq!(bar.from_ref(&*xs[0]).map(|x| x.bar()), Some(2));
+    assert!(bar.from_ref(&*xs[1]).is_none());
+    assert!(foo.from_box(xs.pop().unwrap()).is_err());
+    assert_eq!(foo.get().map.len(), 2);
+}
+
+#[cfg(feature = "use_inventory")]
+#[test]
+fn test_inserting_entry() {
+    use crate::inventory::EntryBuilder;
+
+    let mut registry = Registry::new();
+
+    // Creates the table for `Foo`, then inserts into the existing one
+    (EntryBuilder::inserting_entry(impl_entry!(dyn Foo, A)).insert)(
+        &mut registry,
+        |_, _| true,
+    );
+    (EntryBuilder::inserting_entry(impl_entry!(dyn Foo, B)).insert)(
+        &mut registry,
+        |_, _| true,
+    );
+
+    let foo = registry.cast_into::<dyn Foo>().unwrap();
+    assert_eq!(foo.from_mut(&mut A { x: 1 }).unwrap().foo(), 2);
+    assert_eq!(foo.from_mut(&mut B { y: 1 }).unwrap().foo(), 2);
+}
+
+#[test]
+fn test_registry_try_cast() {
+    use crate::CastError;
+    use std::any::TypeId;
+
+    let registry = make_registry();
+    let bar = "dyn traitcast_core::tests::traits::Bar";
+    let qux = "dyn traitcast_core::tests::traits::Qux";
+
+    let mut a = A { x: 1 };
+    let x: &dyn Bar = registry.try_cast_ref(&a).unwrap();
+    assert_eq!(x.bar(), 1);
+    let x: &mut dyn Foo = registry.try_cast_mut(&mut a).unwrap();
+    assert_eq!(x.foo(), 2);
+    let b: Box<dyn Any> = Box::new(B { y: 3 });
+    let b: Box<dyn Baz> = registry.try_cast_box(b).unwrap();
+    assert_eq!(b.baz(), 3);
+
+    let not_implemented = CastError::ImplNotRegistered {
+        from: TypeId::of::<B>(),
+        into_name: bar,
+    };
+    let b = B { y: 0 };
+    assert_eq!(
+        registry.try_cast_ref::<_, dyn Bar>(&b).err(),
+        Some(not_implemented)
+    );
+    assert_eq!(
+        registry.try_cast_mut::<_, dyn Bar>(&mut B { y: 0 }).err(),
+        Some(not_implemented)
+    );
+    assert_eq!(
+        registry.try_cast_box::<_, dyn Bar>(Box::new(b)).err(),
+        Some(not_implemented)
+    );
+
+    let unregistered = CastError::TraitNotRegistered { into_name: qux };
+    assert_eq!(
+        registry.try_cast_ref::<_, dyn Qux>(&a).err(),
+        Some(unregistered)
+    );
+    assert_eq!(
+        registry.try_cast_mut::<_, dyn Qux>(&mut a).err(),
+        Some(unregistered)
+    );
+    assert_eq!(
+        registry.try_cast_box::<_, dyn Qux>(Box::new(a)).err(),
+        Some(unregistered)
+    );
+}
+
+#[test]
+fn test_cast_from_any_send() {
+    use std::sync::Arc;
+
+    let registry = make_registry();
+
+    let x: &(dyn Any + Send + Sync) = &A { x: 1 };
+    let x: &dyn Bar = registry.try_cast_ref(x).unwrap();
+    assert_eq!(x.bar(), 1);
+
+    let mut b = B { y: 2 };
+    let x: &mut (dyn Any + Send) = &mut b;
+    let x: &mut dyn Foo = registry.try_cast_mut(x).unwrap();
+    assert_eq!(x.foo(), 4);
+    let x: &(dyn Any + Send) = &b;
+    assert!(registry.try_cast_ref::<_, dyn Bar>(x).is_err());
+
+    let x: Arc<dyn Any + Send + Sync> = Arc::new(A { x: 3 });
+    let x: Arc<dyn Bar> = registry
+        .cast_into::<dyn Bar>()
+        .unwrap()
+        .from_arc(x)
+        .ok()
+        .unwrap();
+    assert_eq!(x.bar(), 3);
+}
+
+#[cfg(feature = "use_inventory")]
+#[test]
+fn test_entry_builder_is_sync() {
+    fn _assert_send_sync<T: Send + Sync>() {}
+    _assert_send_sync::<crate::inventory::EntryBuilder>();
+}
+
+/// Randomised checks that a successful cast always yields the original value,
+/// and that a cast succeeds exactly when its pair is registered.
+mod fuzz {
+    use std::any::{Any, TypeId};
+
+    use crate::{impl_entry, ImplEntry, Registry, TraitcastFrom};
+
+    /// A xorshift generator, so that failures are reproducible from the seed.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    macro_rules! impl_trait {
+        ($t:ident, ($($s:ident),+)) => {
+            pub trait $t: TraitcastFrom {
+                fn value(&self) -> u64;
+            }
+            $(
+                impl $t for $s {
+                    fn value(&self) -> u64 {
+                        self.0
+                    }
+                }
+            )+
+        };
+    }
+
+    macro_rules! entries {
+        ($t:ident, ($($s:ident),+)) => {
+            vec![$(impl_entry!(dyn $t, $s)),+]
+        };
+    }
+
+    macro_rules! structs {
+        ($($s:ident),+) => {
+            $(
+                pub struct $s(pub u64);
+            )+
+
+            /// Makes a value of the struct with the given index.
+            fn make(idx: usize, value: u64) -> Box<dyn Any> {
+                let makers: &[fn(u64) -> Box<dyn Any>] =
+                    &[$(|x| Box::new($s(x))),+];
+                makers[idx](value)
+            }
+
+            const STRUCTS: usize = [$(stringify!($s)),+].len();
+
+            /// The `TypeId` of the struct with the given index.
+            fn struct_tid(idx: usize) -> TypeId {
+                [$(TypeId::of::<$s>()),+][idx]
+            }
+        };
+    }
+
+    macro_rules! fuzz_types {
+        (traits: $($t:ident),+; structs: $structs:tt) => {
+            structs! $structs;
+
+            $(
+                impl_trait!($t, $structs);
+            )+
+
+            /// Runs a round of checks against every trait.
+            fn check_all(rng: &mut Rng) {
+                $(
+                    let entries: Vec<ImplEntry<dyn $t>> =
+                        entries!($t, $structs);
+                    check_trait(rng, entries, |x| x.value());
+                )+
+            }
+        };
+    }
+
+    fuzz_types! {
+        traits: T0, T1, T2, T3;
+        structs: (S0, S1, S2, S3, S4, S5, S6, S7)
+    }
+
+    fn check_trait<Tr>(
+        rng: &mut Rng,
+        entries: Vec<ImplEntry<Tr>>,
+        value: fn(&Tr) -> u64,
+    ) where
+        Tr: TraitcastFrom + ?Sized + 'static,
+    {
+        // Register a random subset of the impls
+        let registered: Vec<bool> =
+            (0..STRUCTS).map(|_| rng.below(2) == 0).collect();
+        let mut registry = Registry::new();
+        registry.insert::<Tr>(
+            entries
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| registered[*i])
+                .map(|(_, entry)| entry)
+                .collect(),
+        );
+        let table = registry.cast_into::<Tr>().unwrap();
+
+        for _ in 0..64 {
+            let idx = rng.below(STRUCTS);
+            let expected = rng.next();
+            let mut x = make(idx, expected);
+            let addr = &*x as *const dyn Any as *const ();
+
+            match table.from_ref(&*x) {
+                Some(y) => {
+                    assert!(registered[idx]);
+                    assert_eq!(Any::type_id(y.as_any_ref()), struct_tid(idx));
+                    assert_eq!(y as *const Tr as *const (), addr);
+                    assert_eq!(value(y), expected);
+                }
+                None => assert!(!registered[idx]),
+            }
+
+            match table.from_mut(&mut *x) {
+                Some(y) => {
+                    assert!(registered[idx]);
+                    assert_eq!(Any::type_id(y.as_any_mut()), struct_tid(idx));
+                    assert_eq!(y as *mut Tr as *const (), addr);
+                }
+                None => assert!(!registered[idx]),
+            }
+
+            match table.from_box(x) {
+                Ok(y) => {
+                    assert!(registered[idx]);
+                    assert_eq!(&*y as *const Tr as *const (), addr);
+                    assert_eq!(value(&*y), expected);
+                    assert_eq!(Any::type_id(&*y.as_any_box()), struct_tid(idx));
+                }
+                Err(y) => {
+                    assert!(!registered[idx]);
+                    assert_eq!(Any::type_id(&*y), struct_tid(idx));
+                    assert_eq!(&*y as *const dyn Any as *const (), addr);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz_casts() {
+        for seed in 1..=64_u64 {
+            check_all(&mut Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15)));
+        }
+    }
+}
+
+#[test]
+fn test_registered_traits() {
+    use std::any::TypeId;
+
+    let mut registry = make_registry();
+    let mut tids: Vec<TypeId> = registry.registered_traits().collect();
+    let mut expected = vec![
+        TypeId::of::<dyn Foo>(),
+        TypeId::of::<dyn Bar>(),
+        TypeId::of::<dyn Baz>(),
+    ];
+    tids.sort();
+    expected.sort();
+    assert_eq!(tids, expected);
+
+    // Re-inserting a table does not list the trait twice
+    registry.insert::<dyn Bar>(CastIntoTrait::new());
+    let mut names: Vec<&str> = registry.registered_trait_names().collect();
+    names.sort_unstable();
+    assert_eq!(
+        names,
+        vec![
+            "dyn traitcast_core::tests::traits::Bar",
+            "dyn traitcast_core::tests::traits::Baz",
+            "dyn traitcast_core::tests::traits::Foo",
+        ]
+    );
+}
+
+#[test]
+fn test_impls() {
+    use std::any::TypeId;
+
+    let registry = make_registry();
+    let mut impls: Vec<(TypeId, &str)> =
+        registry.cast_into::<dyn Foo>().unwrap().impls().collect();
+    impls.sort_by_key(|(_, name)| *name);
+    assert_eq!(
+        impls,
+        vec![(TypeId::of::<A>(), "A"), (TypeId::of::<B>(), "B")]
+    );
+
+    let mut names = registry.impls_for::<dyn Foo>().unwrap();
+    names.sort_unstable();
+    assert_eq!(names, vec!["A", "B"]);
+    assert_eq!(registry.impls_for::<dyn Bar>(), Some(vec!["A"]));
+    assert_eq!(registry.impls_for::<dyn Qux>(), None);
+}
+
+#[test]
+fn test_merge() {
+    let mut core = Registry::new();
+    core.insert::<dyn Foo>(vec![impl_entry!(dyn Foo, A)].into_iter().collect());
+
+    let mut user = Registry::new();
+    user.insert::<dyn Foo>(
+        vec![impl_entry!(dyn Foo, B), other::foo_entry()]
+            .into_iter()
+            .collect(),
+    );
+    user.insert::<dyn Bar>(vec![impl_entry!(dyn Bar, A)].into_iter().collect());
+
+    core.merge(user);
+
+    let foo = core.cast_into::<dyn Foo>().unwrap();
+    assert_eq!(foo.map.len(), 3);
+    assert_eq!(foo.from_mut(&mut A { x: 0 }).unwrap().foo(), 1);
+    assert_eq!(foo.from_mut(&mut B { y: 1 }).unwrap().foo(), 2);
+    assert!(core
+        .cast_into::<dyn Bar>()
+        .unwrap()
+        .from_ref(&A { x: 0 })
+        .is_some());
+    assert_eq!(core.audit().trait_count, 2);
+}
+
+#[test]
+fn test_merge_other_wins() {
+    let mut first = Registry::new();
+    first
+        .insert::<dyn Foo>(vec![impl_entry!(dyn Foo, A)].into_iter().collect());
+
+    // A hand-made entry for `A` which can never cast
+    let mut entry = impl_entry!(dyn Foo, A);
+    entry.cast_ref = |_| None;
+    let mut second = Registry::new();
+    second.insert::<dyn Foo>(vec![entry].into_iter().collect());
+
+    first.merge(second);
+    let foo = first.cast_into::<dyn Foo>().unwrap();
+    assert!(foo.from_ref(&A { x: 0 }).is_none());
+}
+
+#[cfg(feature = "use_inventory")]
+#[test]
+fn test_idempotent_registration() {
+    use crate::inventory::EntryBuilder;
+
+    let entry = impl_entry!(dyn Foo, A);
+    let copy = entry;
+    assert!(entry == copy);
+    assert!(entry != impl_entry!(dyn Foo, B));
+
+    let mut registry = Registry::new();
+    for _ in 0..3 {
+        (EntryBuilder::inserting_entry(entry).insert)(&mut registry, |_, _| {
+            true
+        });
+    }
+    let foo = registry.cast_into::<dyn Foo>().unwrap();
+    assert_eq!(foo.map.len(), 1);
+    assert!(foo.map.get(&entry.tid) == Some(&entry));
+
+    // Separate invocations for the same pair also share one slot
+    (EntryBuilder::inserting_entry(impl_entry!(dyn Foo, A)).insert)(
+        &mut registry,
+        |_, _| true,
+    );
+    assert_eq!(registry.cast_into::<dyn Foo>().unwrap().map.len(), 1);
+}
+
+#[cfg(feature = "strict_registration")]
+#[test]
+fn test_strict_registration() {
+    use crate::inventory::EntryBuilder;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    /// Names `A` differently, as a path or alias registered twice would.
+    type AliasA = A;
+
+    let first = EntryBuilder::inserting_entry(impl_entry!(dyn Foo, A));
+    let second = EntryBuilder::inserting_entry(impl_entry!(dyn Foo, A));
+    let alias = EntryBuilder::inserting_entry(impl_entry!(dyn Foo, AliasA));
+    let mut registry = Registry::new();
+
+    // Applying the same builder twice, or an identical one, is fine
+    (first.insert)(&mut registry, |_, _| true);
+    (first.insert)(&mut registry, |_, _| true);
+    (second.insert)(&mut registry, |_, _| true);
+    (EntryBuilder::inserting_entry(impl_entry!(dyn Foo, B)).insert)(
+        &mut registry,
+        |_, _| true,
+    );
+
+    let err = catch_unwind(AssertUnwindSafe(|| {
+        (alias.insert)(&mut registry, |_, _| true)
+    }))
+    .unwrap_err();
+    let message = err.downcast::<String>().unwrap();
+    assert_eq!(
+        *message,
+        "conflicting registrations for casting 'AliasA' into 'dyn Foo': it \
+         was already registered as casting 'A' into 'dyn Foo'"
+    );
+}
+
+#[test]
+fn test_register_impl() {
+    let mut registry = Registry::new();
+    registry.register_impl(impl_entry!(dyn Bar, A));
+    registry.register_impl(impl_entry!(dyn Foo, B));
+
+    let a: Box<dyn Any> = Box::new(A { x: 4 });
+    let bar = registry.cast_into::<dyn Bar>().unwrap();
+    assert_eq!(bar.from_ref(&*a).unwrap().bar(), 4);
+
+    // Adds to the existing table rather than replacing it
+    registry.register_impl(impl_entry!(dyn Foo, A));
+    let foo = registry.cast_into::<dyn Foo>().unwrap();
+    assert_eq!(foo.map.len(), 2);
+    assert!(foo.from_ref(&*a).is_some());
+    assert!(foo.from_ref(&B { y: 0 }).is_some());
+}
+
+#[cfg(feature = "downcast_rs_compat")]
+#[test]
+fn test_downcast_rs_compat() {
+    use crate::downcast_rs::Downcast;
+
+    trait Widget: Downcast {}
+    impl Widget for A {}
+    crate::traitcast_downcast_rs!(Widget);
+
+    let registry = make_registry();
+    let bar = registry.cast_into::<dyn Bar>().unwrap();
+
+    let w: Box<dyn Widget> = Box::new(A { x: 6 });
+    assert_eq!(bar.from_ref(&*w).unwrap().bar(), 6);
+    let w: Box<dyn Bar> = bar.from_box(w).ok().unwrap();
+    assert_eq!(w.bar(), 6);
+
+    // `dyn Downcast` itself can be cast from, too
+    let mut d: Box<dyn Downcast> = Box::new(A { x: 7 });
+    let foo = registry.cast_into::<dyn Foo>().unwrap();
+    assert_eq!(foo.from_mut(&mut *d).unwrap().foo(), 8);
+}
+
+#[test]
+fn test_remove() {
+    use std::any::TypeId;
+
+    let mut registry = make_registry();
+
+    let entry = registry.remove_impl::<dyn Foo>(TypeId::of::<A>()).unwrap();
+    assert_eq!(entry.tid, TypeId::of::<A>());
+    assert!(registry.remove_impl::<dyn Foo>(TypeId::of::<A>()).is_none());
+    assert!(registry.remove_impl::<dyn Qux>(TypeId::of::<A>()).is_none());
+    let foo = registry.cast_into::<dyn Foo>().unwrap();
+    assert!(foo.from_ref(&A { x: 0 }).is_none());
+    assert!(foo.from_ref(&B { y: 0 }).is_some());
+
+    let table = registry.remove_trait::<dyn Bar>().unwrap();
+    assert_eq!(table.map.len(), 1);
+    assert!(registry.cast_into::<dyn Bar>().is_none());
+    assert!(registry.remove_trait::<dyn Bar>().is_none());
+    assert_eq!(registry.audit().trait_count, 2);
+}
+
+#[test]
+fn test_hash_entries() {
+    use crate::ImplEntry;
+    use std::collections::HashSet;
+
+    let a = impl_entry!(dyn Foo, A);
+    let b = impl_entry!(dyn Foo, B);
+    let set: HashSet<ImplEntry<dyn Foo>> =
+        vec![a, b, a, a, b].into_iter().collect();
+    assert_eq!(set.len(), 2);
+    assert!(set.contains(&a));
+}
+
+#[test]
+fn test_traits_for() {
+    use std::any::TypeId;
+
+    let mut registry = make_registry();
+    let sorted = |mut tids: Vec<TypeId>| {
+        tids.sort();
+        tids
+    };
+    let a_traits =
+        sorted(vec![TypeId::of::<dyn Foo>(), TypeId::of::<dyn Bar>()]);
+
+    assert_eq!(sorted(registry.traits_for(TypeId::of::<A>())), a_traits);
+    let mut names = registry.trait_names_for(TypeId::of::<B>());
+    names.sort_unstable();
+    assert_eq!(
+        names,
+        vec![
+            "dyn traitcast_core::tests::traits::Baz",
+            "dyn traitcast_core::tests::traits::Foo",
+        ]
+    );
+    assert!(registry.traits_for(TypeId::of::<other::A>()).is_empty());
+
+    // The cached index gives the same answers
+    let index = registry.build_reverse_index();
+    assert_eq!(sorted(index[&TypeId::of::<A>()].clone()), a_traits);
+    assert_eq!(sorted(registry.traits_for(TypeId::of::<A>())), a_traits);
+
+    // Modifying the registry drops the stale index
+    registry.remove_impl::<dyn Bar>(TypeId::of::<A>());
+    assert_eq!(
+        registry.traits_for(TypeId::of::<A>()),
+        vec![TypeId::of::<dyn Foo>()]
+    );
+}
+
+#[test]
+fn test_probe() {
+    use std::any::TypeId;
+
+    let registry = make_registry();
+    assert!(registry.probe::<dyn Foo>(TypeId::of::<A>()));
+    assert!(registry.probe::<dyn Baz>(TypeId::of::<B>()));
+    assert!(!registry.probe::<dyn Baz>(TypeId::of::<A>()));
+    assert!(!registry.probe::<dyn Qux>(TypeId::of::<A>()));
+}
+
+#[test]
+fn test_can_cast() {
+    use std::any::TypeId;
+
+    let registry = make_registry();
+    assert!(registry.is_trait_registered::<dyn Foo>());
+    assert!(!registry.is_trait_registered::<dyn Qux>());
+
+    let foo = TypeId::of::<dyn Foo>();
+    let baz = TypeId::of::<dyn Baz>();
+    assert!(registry.can_cast(TypeId::of::<A>(), foo));
+    assert!(!registry.can_cast(TypeId::of::<A>(), baz));
+    assert!(!registry.can_cast(TypeId::of::<A>(), TypeId::of::<dyn Qux>()));
+}
+
+#[test]
+fn test_schema() {
+    let registry = make_registry();
+    let names = |schema: &[crate::schema::EntrySchema]| {
+        schema
+            .iter()
+            .map(|e| (e.from_name, e.into_name))
+            .collect::<Vec<_>>()
+    };
+
+    let foo = registry.cast_into::<dyn Foo>().unwrap().schema();
+    assert_eq!(names(&foo), vec![("dyn Foo", "A"), ("dyn Foo", "B")]);
+
+    let schema = registry.schema();
+    assert_eq!(
+        names(&schema.entries),
+        vec![
+            ("dyn Bar", "A"),
+            ("dyn Baz", "B"),
+            ("dyn Foo", "A"),
+            ("dyn Foo", "B"),
+        ]
+    );
+
+    // The same concrete type has the same hash in every table
+    assert_eq!(schema.entries[0].tid, schema.entries[2].tid);
+    assert_ne!(schema.entries[2].tid, schema.entries[3].tid);
+}
+
+#[test]
+fn test_to_mermaid() {
+    let mermaid = make_registry().to_mermaid();
+    let lines: Vec<&str> = mermaid.lines().collect();
+    assert_eq!(lines[0], "graph LR");
+
+    // Nodes are numbered in name order
+    for node in &[
+        "n0[\"A\"]",
+        "n1[\"B\"]",
+        "n2[\"dyn Bar\"]",
+        "n3[\"dyn Baz\"]",
+        "n4[\"dyn Foo\"]",
+    ] {
+        assert!(lines.contains(&&format!("    {}", node)[..]), "{}", node);
+    }
+
+    let edges: Vec<&str> = lines
+        .iter()
+        .filter(|l| l.contains("-->"))
+        .cloned()
+        .collect();
+    assert_eq!(
+        edges,
+        vec![
+            "    n0 --> n2",
+            "    n1 --> n3",
+            "    n0 --> n4",
+            "    n1 --> n4"
+        ]
+    );
+
+    assert_eq!(Registry::new().to_mermaid(), "graph LR\n");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_schema_json() {
+    let registry = make_registry();
+    let json = serde_json::to_string(&registry.schema()).unwrap();
+    let tid = registry.schema().entries[1].tid;
+    assert!(json.starts_with(r#"{"entries":[{"from_name":"dyn Bar","#));
+    assert!(json.contains(&format!(
+        r#"{{"from_name":"dyn Baz","into_name":"B","tid":{}}}"#,
+        tid
+    )));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serialize_trait_object() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+
+    impl Bar for Point {
+        fn bar(&self) -> i64 {
+            self.x + self.y
+        }
+    }
+
+    let mut registry = make_registry();
+    registry.register_impl(impl_entry!(dyn Bar, Point));
+    let to_json = |x: &(dyn Bar + 'static)| {
+        let mut out = Vec::new();
+        registry
+            .serialize_trait_object(
+                x,
+                &mut serde_json::Serializer::new(&mut out),
+            )
+            .map(|()| String::from_utf8(out).unwrap())
+            .map_err(|err| err.to_string())
+    };
+
+    assert_eq!(
+        to_json(&Point { x: 1, y: 2 }).unwrap(),
+        r#"{"Point":{"x":1,"y":2}}"#
+    );
+    assert_eq!(
+        to_json(&A { x: 1 }).unwrap_err(),
+        "'A' does not implement Serialize"
+    );
+    assert!(Registry::new()
+        .serialize_trait_object::<dyn Bar, _>(
+            &Point { x: 1, y: 2 },
+            &mut serde_json::Serializer::new(Vec::new())
+        )
+        .is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_deserialize_trait_object() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+
+    impl Bar for Point {
+        fn bar(&self) -> i64 {
+            self.x + self.y
+        }
+    }
+
+    /// Can be written, but not read back.
+    #[derive(serde::Serialize)]
+    struct Label;
+
+    impl Bar for Label {
+        fn bar(&self) -> i64 {
+            0
+        }
+    }
+
+    let mut registry = make_registry();
+    registry.register_impl(impl_entry!(dyn Bar, Point));
+    registry.register_impl(impl_entry!(dyn Bar, Label));
+    let from_json = |json: &str| {
+        let mut de = serde_json::Deserializer::from_str(json);
+        registry
+            .deserialize_tagged_trait_object::<dyn Bar, _>(&mut de)
+            .map_err(|err| err.to_string())
+    };
+
+    let x = from_json(r#"{"Point":{"x":1,"y":2}}"#).unwrap();
+    assert_eq!(x.bar(), 3);
+    assert!(x.as_any_ref().is::<Point>());
+
+    // Round trip through `serialize_trait_object`
+    let mut out = Vec::new();
+    registry
+        .serialize_trait_object(&*x, &mut serde_json::Serializer::new(&mut out))
+        .unwrap();
+    let x = from_json(std::str::from_utf8(&out).unwrap()).unwrap();
+    assert_eq!(x.bar(), 3);
+
+    // With the tag stored separately
+    let mut de = serde_json::Deserializer::from_str(r#"{"x":3,"y":4}"#);
+    let x: Box<dyn Bar> =
+        registry.deserialize_trait_object("Point", &mut de).unwrap();
+    assert_eq!(x.bar(), 7);
+
+    assert!(from_json(r#"{"B":{}}"#)
+        .err()
+        .unwrap()
+        .starts_with("no type named 'B' is registered"));
+    assert!(from_json(r#"{"A":{}}"#)
+        .err()
+        .unwrap()
+        .starts_with("'A' does not implement Deserialize"));
+    assert!(from_json(r#"{"Label":null}"#)
+        .err()
+        .unwrap()
+        .starts_with("'Label' does not implement Deserialize"));
+    assert!(from_json(r#"{"Point":{"x":1,"y":2},"A":{}}"#).is_err());
+    assert!(from_json("{}").is_err());
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_trace_cast_failure() {
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Records the fields of every event as `name=value` strings.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for Recorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            let mut events = self.0.lock().unwrap();
+            let event = events.last_mut().unwrap();
+            event.push_str(&format!("{}={:?} ", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event) {
+            self.0.lock().unwrap().push(String::new());
+            event.record(&mut self.clone());
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let registry = make_registry();
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let b = B { y: 1 };
+        assert!(registry
+            .cast_into::<dyn Bar>()
+            .unwrap()
+            .from_ref(&b)
+            .is_none());
+        assert!(registry.try_cast_ref::<_, dyn Qux>(&b).is_err());
+        assert!(registry.try_cast_ref::<_, dyn Foo>(&b).is_ok());
+    });
+
+    let events = recorder.0.lock().unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(events[0].contains("reason=\"impl not registered\""));
+    assert!(
+        events[0].contains(&format!("from={:?}", std::any::TypeId::of::<B>()))
+    );
+    assert!(events[0]
+        .contains("into_name=\"dyn traitcast_core::tests::traits::Bar\""));
+    assert!(events[1].contains("reason=\"trait not registered\""));
+}
+
+#[test]
+fn test_opcode_table() {
+    use crate::opcode::OpcodeTable;
+
+    const FOO_A: u16 = 0;
+    const FOO_B: u16 = 1;
+    const BAR_A: u16 = 3;
+
+    let mut table = OpcodeTable::new();
+    table.register(FOO_A, impl_entry!(dyn Foo, A));
+    table.register(FOO_B, impl_entry!(dyn Foo, B));
+    table.register(BAR_A, impl_entry!(dyn Bar, A));
+
+    let mut a: Box<dyn Any> = Box::new(A { x: 5 });
+    let mut b: Box<dyn Any> = Box::new(B { y: 6 });
+    assert_eq!(
+        table.cast_mut::<dyn Foo, _>(FOO_A, &mut *a).unwrap().foo(),
+        6
+    );
+    assert_eq!(
+        table.cast_mut::<dyn Foo, _>(FOO_B, &mut *b).unwrap().foo(),
+        12
+    );
+    assert_eq!(table.cast_ref::<dyn Bar, _>(BAR_A, &*a).unwrap().bar(), 6);
+
+    // Wrong concrete type or trait for the opcode
+    assert!(table.cast_ref::<dyn Foo, _>(FOO_A, &*b).is_none());
+    assert!(table.cast_ref::<dyn Bar, _>(FOO_A, &*a).is_none());
+
+    // A gap in the opcode space, and an opcode past the end
+    assert!(!table.contains(2));
+    assert!(table.cast_ref::<dyn Bar, _>(2, &*a).is_none());
+    assert!(table.cast_ref::<dyn Bar, _>(u16::MAX, &*a).is_none());
+
+    // Replacing and removing entries
+    table.register(FOO_A, impl_entry!(dyn Bar, A));
+    assert!(table.cast_ref::<dyn Bar, _>(FOO_A, &*a).is_some());
+    assert!(table.unregister(FOO_A));
+    assert!(!table.unregister(FOO_A));
+    assert!(table.cast_ref::<dyn Bar, _>(FOO_A, &*a).is_none());
+}
+
+#[test]
+fn test_with_hasher() {
+    use std::hash::BuildHasherDefault;
+
+    /// Not a good hasher, but any hasher will do.
+    #[derive(Default)]
+    struct XorHasher(u64);
+
+    impl std::hash::Hasher for XorHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &b in bytes {
+                self.0 = self.0.rotate_left(8) ^ u64::from(b);
+            }
+        }
+    }
+
+    let mut table: CastIntoTrait<dyn Foo, BuildHasherDefault<XorHasher>> =
+        CastIntoTrait::with_hasher(Default::default());
+    table.extend(vec![impl_entry!(dyn Foo, A)].into_iter().collect());
+    assert_eq!(table.from_mut(&mut A { x: 1 }).unwrap().foo(), 2);
+    assert!(table.from_mut(&mut B { y: 1 }).is_none());
+    assert_eq!(table.impls().count(), 1);
+}
+
+#[test]
+fn test_small_table() {
+    use crate::SMALL_TABLE_CUTOFF;
+
+    /// Some other types to fill up a table with.
+    trait Marker {}
+    impl<T> Marker for T {}
+    struct T<const N: usize>;
+
+    // Tables on either side of the cutoff find the same entries
+    let entries = vec![
+        impl_entry!(dyn Marker, A),
+        impl_entry!(dyn Marker, B),
+        impl_entry!(dyn Marker, T<0>),
+        impl_entry!(dyn Marker, T<1>),
+        impl_entry!(dyn Marker, T<2>),
+        impl_entry!(dyn Marker, T<3>),
+        impl_entry!(dyn Marker, T<4>),
+        impl_entry!(dyn Marker, T<5>),
+        impl_entry!(dyn Marker, T<6>),
+        impl_entry!(dyn Marker, T<7>),
+    ];
+    assert!(entries.len() > SMALL_TABLE_CUTOFF);
+    for n in 1..=entries.len() {
+        let table: CastIntoTrait<dyn Marker> =
+            entries.iter().take(n).cloned().collect();
+        assert!(table.from_ref(&A { x: 0 }).is_some());
+        assert_eq!(table.from_ref(&B { y: 0 }).is_some(), n >= 2);
+        assert_eq!(table.from_ref(&T::<7>).is_some(), n == entries.len());
+        assert!(table.from_ref(&0u8).is_none());
+    }
+}
+
+#[test]
+fn test_identity_fast_path() {
+    use std::any::TypeId;
+
+    // Only `B` is registered for `Foo`
+    let mut registry = Registry::new();
+    registry
+        .insert::<dyn Foo>(vec![impl_entry!(dyn Foo, B)].into_iter().collect());
+    registry
+        .insert::<dyn Bar>(vec![impl_entry!(dyn Bar, A)].into_iter().collect());
+    assert!(!registry.identity_fast_path());
+
+    let mut a = A { x: 1 };
+    let unregistered = TraitcastError::ImplNotRegistered {
+        from: TypeId::of::<A>(),
+        into_name: std::any::type_name::<dyn Foo>(),
+    };
+
+    // Strict by default, so identity casts need registering
+    {
+        let x: &mut dyn Foo = &mut a;
+        assert_eq!(
+            registry.try_cast_ref::<_, dyn Foo>(&*x).err(),
+            Some(unregistered)
+        );
+        assert_eq!(
+            registry.try_cast_mut::<_, dyn Foo>(x).err(),
+            Some(unregistered)
+        );
+        let x: Box<dyn Foo> = Box::new(A { x: 1 });
+        assert_eq!(
+            registry.try_cast_box::<_, dyn Foo>(x).err(),
+            Some(unregistered)
+        );
+        let x: &dyn Bar = &a;
+        assert_eq!(
+            Registry::new().try_cast_ref::<_, dyn Bar>(x).err(),
+            Some(TraitcastError::TraitNotRegistered {
+                into_name: std::any::type_name::<dyn Bar>()
+            })
+        );
+    }
+
+    // With the fast path, identity casts always succeed
+    registry.set_identity_fast_path(true);
+    {
+        let x: &mut dyn Foo = &mut a;
+        assert!(registry.try_cast_ref::<_, dyn Foo>(&*x).is_ok());
+        assert_eq!(registry.try_cast_mut::<_, dyn Foo>(x).unwrap().foo(), 2);
+        let x: Box<dyn Foo> = Box::new(A { x: 1 });
+        assert!(registry.try_cast_box::<_, dyn Foo>(x).is_ok());
+    }
+
+    // Even without a table for the trait
+    let mut empty = Registry::new();
+    empty.set_identity_fast_path(true);
+    let x: &dyn Bar = &a;
+    assert_eq!(empty.try_cast_ref::<_, dyn Bar>(x).unwrap().bar(), 2);
+
+    // Casts into other traits are looked up either way
+    for &enabled in &[false, true] {
+        registry.set_identity_fast_path(enabled);
+        let x: &mut dyn Foo = &mut a;
+        assert_eq!(registry.try_cast_ref::<_, dyn Bar>(&*x).unwrap().bar(), 2);
+        let x: &mut dyn Bar = registry.try_cast_mut(x).unwrap();
+        assert!(registry.try_cast_mut::<_, dyn Foo>(x).is_err());
+    }
+}
+
+#[test]
+fn test_strict() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    // `Foo` has impls, `Bar` has an empty table and `Baz` has none at all
+    let mut registry = Registry::new();
+    registry
+        .insert::<dyn Foo>(vec![impl_entry!(dyn Foo, B)].into_iter().collect());
+    registry.insert::<dyn Bar>(CastIntoTrait::new());
+    assert!(!registry.strict());
+
+    // Lenient by default
+    let a = A { x: 1 };
+    assert!(registry.try_cast_ref::<_, dyn Bar>(&a).is_err());
+    assert!(registry.try_cast_ref::<_, dyn Baz>(&a).is_err());
+
+    registry.set_strict(true);
+    let panic_message = |f: &dyn Fn()| -> String {
+        let err = catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+        err.downcast::<String>().map(|x| *x).unwrap()
+    };
+    let message = panic_message(&|| {
+        let _ = registry.try_cast_ref::<_, dyn Bar>(&a);
+    });
+    assert!(message.contains("has no impls"), "{}", message);
+    let message = panic_message(&|| {
+        let _ = registry.try_cast_box::<_, dyn Baz>(Box::new(A { x: 1 }));
+    });
+    assert!(message.contains("is not registered"), "{}", message);
+
+    // An impl missing from a non-empty table is an ordinary failure
+    assert!(registry.try_cast_ref::<_, dyn Foo>(&a).is_err());
+    assert!(registry.try_cast_ref::<_, dyn Foo>(&B { y: 1 }).is_ok());
+
+    registry.set_strict(false);
+    assert!(registry.try_cast_ref::<_, dyn Baz>(&a).is_err());
+}
+
+#[test]
+fn test_is_superset_of() {
+    let registry = make_registry();
+    assert!(registry.is_superset_of(&registry));
+    assert!(registry.is_superset_of(&Registry::new()));
+
+    // A subset, which also has an empty table the superset lacks
+    let mut old = Registry::new();
+    old.insert::<dyn Foo>(vec![impl_entry!(dyn Foo, B)].into_iter().collect());
+    old.insert::<dyn Qux>(CastIntoTrait::new());
+    assert!(registry.is_superset_of(&old));
+    assert!(!old.is_superset_of(&registry));
+
+    // Missing a single entry is enough to fail
+    let mut removed = make_registry();
+    removed.remove_impl::<dyn Foo>(std::any::TypeId::of::<B>());
+    assert!(!removed.is_superset_of(&registry));
+    assert!(registry.is_superset_of(&removed));
+}
+
+#[test]
+fn test_cast_into_any() {
+    // `dyn Any` needs no table, even in an empty registry
+    let registry = Registry::new();
+    let mut a = A { x: 1 };
+
+    let x: &dyn Foo = &a;
+    let any: &dyn Any = registry.try_cast_ref(x).unwrap();
+    assert_eq!(any.downcast_ref::<A>().unwrap().x, 1);
+
+    let x: &mut dyn Foo = &mut a;
+    let any: &mut dyn Any = registry.try_cast_mut(x).unwrap();
+    any.downcast_mut::<A>().unwrap().x = 2;
+    assert_eq!(a.x, 2);
+
+    let x: Box<dyn Foo> = Box::new(B { y: 3 });
+    let any: Box<dyn Any> = registry.try_cast_box(x).unwrap();
+    assert_eq!(any.downcast::<B>().unwrap().y, 3);
+}
+
+#[test]
+fn test_with_std_traits() {
+    use std::any::TypeId;
+    use std::fmt::{Debug, Display};
+
+    #[derive(Debug)]
+    struct Point;
+
+    let mut registry = Registry::with_std_traits();
+    crate::register_std_traits!(registry; i32, String);
+    crate::register_std_traits!(registry; Debug: Point);
+
+    let x: Box<dyn Any> = Box::new(String::from("hi"));
+    let display: &dyn Display = registry.try_cast_ref(&*x).unwrap();
+    assert_eq!(display.to_string(), "hi");
+    let debug: &dyn Debug = registry.try_cast_ref(&*x).unwrap();
+    assert_eq!(format!("{:?}", debug), "\"hi\"");
+
+    let p: Box<dyn Any> = Box::new(Point);
+    let debug: Box<dyn Debug> = registry.try_cast_box(p).unwrap();
+    assert_eq!(format!("{:?}", debug), "Point");
+
+    // The tables exist even for types that were not registered
+    let p: &dyn Any = &Point;
+    assert_eq!(
+        registry.try_cast_ref::<_, dyn Display>(p).err(),
+        Some(TraitcastError::ImplNotRegistered {
+            from: TypeId::of::<Point>(),
+            into_name: std::any::type_name::<dyn Display>(),
+        })
+    );
+    let any: &dyn Any = registry.try_cast_ref(p).unwrap();
+    assert!(any.is::<Point>());
+}
+
+#[test]
+fn test_register_all() {
+    use crate::RegisterSummary;
+
+    let mut registry = Registry::new();
+    assert!(!registry.register_impl(impl_entry!(dyn Foo, A)));
+    assert!(registry.register_impl(impl_entry!(dyn Foo, A)));
+
+    // `A` is already registered, `B` is new
+    let summary = registry
+        .register_all(vec![impl_entry!(dyn Foo, A), impl_entry!(dyn Foo, B)]);
+    assert_eq!(
+        summary,
+        RegisterSummary {
+            inserted: 1,
+            replaced: 1
+        }
+    );
+
+    let summary = registry.register_all(vec![impl_entry!(dyn Bar, A)]);
+    assert_eq!(
+        summary,
+        RegisterSummary {
+            inserted: 1,
+            replaced: 0
+        }
+    );
+    assert_eq!(
+        registry.register_all(Vec::<crate::ImplEntry<dyn Baz>>::new()),
+        RegisterSummary::default()
+    );
+    assert_eq!(registry.impls_for::<dyn Foo>().unwrap().len(), 2);
+}
+
+#[test]
+fn test_freeze() {
+    use crate::frozen::Allowlist;
+    use std::any::TypeId;
+
+    let mut allowlist = Allowlist::new();
+    allowlist
+        .allow_type::<dyn Foo, A>()
+        .allow::<dyn Baz>(TypeId::of::<B>());
+    let frozen = make_registry().freeze(&allowlist);
+
+    let a: Box<dyn Any> = Box::new(A { x: 1 });
+    let b: Box<dyn Any> = Box::new(B { y: 2 });
+    assert!(frozen.try_cast_ref::<_, dyn Foo>(&*a).is_ok());
+    assert_eq!(
+        frozen.try_cast_ref::<_, dyn Foo>(&*b).err(),
+        Some(TraitcastError::ImplNotRegistered {
+            from: TypeId::of::<B>(),
+            into_name: std::any::type_name::<dyn Foo>(),
+        })
+    );
+
+    // `Bar` is not mentioned at all, so its table is emptied but kept
+    assert_eq!(frozen.impls_for::<dyn Bar>(), Some(vec![]));
+    assert!(frozen.try_cast_ref::<_, dyn Bar>(&*a).is_err());
+
+    let baz: Box<dyn Baz> = frozen.try_cast_box(b).unwrap();
+    assert_eq!(baz.baz(), 2);
+    assert!(!allowlist.is_allowed(TypeId::of::<A>(), TypeId::of::<dyn Baz>()));
+}
+
+#[test]
+fn test_debug() {
+    use std::any::{type_name, TypeId};
+
+    let registry = make_registry();
+    assert_eq!(
+        format!("{:?}", registry),
+        format!(
+            "Registry {{ {}: [\"A\"], {}: [\"B\"], {}: [\"A\", \"B\"] }}",
+            type_name::<dyn Bar>(),
+            type_name::<dyn Baz>(),
+            type_name::<dyn Foo>(),
+        )
+    );
+    assert_eq!(format!("{:?}", Registry::new()), "Registry");
+
+    let foo = registry.cast_into::<dyn Foo>().unwrap();
+    assert_eq!(
+        format!("{:?}", foo),
+        format!(
+            "CastIntoTrait {{ A: {:?}, B: {:?} }}",
+            TypeId::of::<A>(),
+            TypeId::of::<B>()
+        )
+    );
+}
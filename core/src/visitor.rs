@@ -0,0 +1,73 @@
+/*!
+This module defines `TypeVisitor`, which dispatches on the concrete type of a
+castable object rather than on the traits it implements.
+*/
+use core::any::{Any, TypeId};
+
+use crate::prelude::*;
+use crate::HashMap;
+
+use crate::TraitcastFrom;
+
+/// Maps concrete types to handlers, and calls the handler matching the
+/// concrete type of a visited object.
+///
+/// # Example
+/// ```
+/// # use traitcast_core::visitor::TypeVisitor;
+/// let visitor = TypeVisitor::new()
+///     .on(|x: &i32| format!("int {}", x))
+///     .on(|x: &String| format!("string {}", x));
+///
+/// let x: Box<dyn std::any::Any> = Box::new(7);
+/// assert_eq!(visitor.visit(&*x), Some("int 7".to_string()));
+///
+/// // Types without a handler fall through
+/// assert_eq!(visitor.visit(&7.5), None);
+/// ```
+pub struct TypeVisitor<'a, R> {
+    handlers: HashMap<TypeId, Handler<'a, R>>,
+}
+
+/// A handler which downcasts its argument to the type it was registered for.
+type Handler<'a, R> = Box<dyn Fn(&dyn Any) -> Option<R> + 'a>;
+
+impl<'a, R> TypeVisitor<'a, R> {
+    /// Makes a new visitor with no handlers.
+    pub fn new() -> Self {
+        TypeVisitor {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Adds a handler for objects whose concrete type is `T`, replacing any
+    /// existing handler for `T`.
+    pub fn on<T, F>(mut self, f: F) -> Self
+    where
+        T: Any,
+        F: Fn(&T) -> R + 'a,
+    {
+        self.handlers.insert(
+            TypeId::of::<T>(),
+            Box::new(move |x| x.downcast_ref::<T>().map(&f)),
+        );
+        self
+    }
+
+    /// Calls the handler for the concrete type of `x`. Returns `None` if
+    /// there is no handler for that type.
+    pub fn visit<From>(&self, x: &From) -> Option<R>
+    where
+        From: TraitcastFrom + ?Sized,
+    {
+        let x = x.as_any_ref();
+        let handler = self.handlers.get(&x.type_id())?;
+        handler(x)
+    }
+}
+
+impl<'a, R> Default for TypeVisitor<'a, R> {
+    fn default() -> Self {
+        TypeVisitor::new()
+    }
+}
@@ -0,0 +1,202 @@
+/*!
+This crate provides `#[derive(Traitcastable)]`, which registers a type with
+the global registry of `traitcast`, along with the traits it may be cast
+into, and `#[traitcast_trait]`, which makes a trait castable out of. Use them
+through the `derive` feature of `traitcast`.
+*/
+
+use proc_macro::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, parse_quote, DeriveInput, ItemTrait, Path, Token,
+    TypeParamBound,
+};
+
+/// Registers the type with the global registry, so that it can be cast into,
+/// and registers its impls of the traits listed in `#[traitcast(...)]`. This
+/// is equivalent to `traitcast!(struct A: Foo, Bar)`, or to
+/// `traitcast!(struct A)` without the attribute, so like those it needs the
+/// crate to depend on `inventory` as well.
+///
+/// Generic types can't be registered, because each instantiation needs its
+/// own registration.
+///
+/// # Example
+/// ```
+/// use traitcast::{Traitcast, TraitcastFrom};
+/// use traitcast_derive::Traitcastable;
+///
+/// trait Foo: TraitcastFrom {
+///     fn foo(&self) -> i32;
+/// }
+///
+/// trait Bar: TraitcastFrom {
+///     fn bar(&self) -> i32;
+/// }
+///
+/// #[derive(Traitcastable)]
+/// #[traitcast(Foo, Bar)]
+/// struct A {
+///     x: i32,
+/// }
+///
+/// impl Foo for A {
+///     fn foo(&self) -> i32 {
+///         self.x
+///     }
+/// }
+///
+/// impl Bar for A {
+///     fn bar(&self) -> i32 {
+///         -self.x
+///     }
+/// }
+///
+/// let x: &dyn Foo = &A { x: 7 };
+/// let y: &dyn Bar = x.cast_ref().unwrap();
+/// assert_eq!(y.bar(), -7);
+/// ```
+///
+/// Listing a trait the type does not implement is an error, which points at
+/// the trait in the attribute.
+/// ```compile_fail
+/// use traitcast::TraitcastFrom;
+/// use traitcast_derive::Traitcastable;
+///
+/// trait Foo: TraitcastFrom {}
+///
+/// #[derive(Traitcastable)]
+/// #[traitcast(Foo)]
+/// struct A;
+/// ```
+#[proc_macro_derive(Traitcastable, attributes(traitcast))]
+pub fn derive_traitcastable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "Traitcastable can't be derived for generic types; register each \
+             instantiation with `traitcast!` instead",
+        ));
+    }
+
+    let name = &input.ident;
+    let traits = traits(input)?;
+    if traits.is_empty() {
+        return Ok(quote! {
+            ::traitcast::traitcast!(struct #name);
+        });
+    }
+
+    // Checked separately, so that a missing impl is reported at the trait in
+    // the attribute rather than somewhere inside `traitcast!`
+    let checks = traits.iter().map(|tr| {
+        quote_spanned! {tr.span()=>
+            const _: fn() = || {
+                fn implements<T: ?Sized + #tr>() {}
+                implements::<#name>();
+            };
+        }
+    });
+
+    Ok(quote! {
+        #(#checks)*
+        ::traitcast::traitcast!(struct #name: #(#traits),*);
+    })
+}
+
+/// Collects the traits listed in every `#[traitcast(...)]` attribute.
+fn traits(input: &DeriveInput) -> syn::Result<Vec<Path>> {
+    let mut traits = Vec::new();
+    for attr in &input.attrs {
+        if attr.path().is_ident("traitcast") {
+            let paths = attr.parse_args_with(
+                Punctuated::<Path, Token![,]>::parse_terminated,
+            )?;
+            traits.extend(paths);
+        }
+    }
+    Ok(traits)
+}
+
+/// Makes a trait castable out of, by adding `TraitcastFrom` to its
+/// supertraits unless it is there already. Tables are created when the first
+/// impl is registered, so the trait needs no registration of its own.
+///
+/// # Example
+/// ```
+/// use traitcast::{traitcast, Traitcast};
+/// use traitcast_derive::traitcast_trait;
+///
+/// #[traitcast_trait]
+/// trait Foo {
+///     fn foo(&self) -> i32;
+/// }
+///
+/// #[traitcast_trait]
+/// trait Baz {
+///     fn baz(self: Box<Self>) -> i32;
+/// }
+///
+/// struct A {
+///     x: i32,
+/// }
+///
+/// impl Foo for A {
+///     fn foo(&self) -> i32 {
+///         self.x
+///     }
+/// }
+///
+/// impl Baz for A {
+///     fn baz(self: Box<Self>) -> i32 {
+///         -self.x
+///     }
+/// }
+///
+/// traitcast!(struct A: Foo, Baz);
+///
+/// let x: Box<dyn Foo> = Box::new(A { x: 7 });
+/// assert_eq!(x.foo(), 7);
+/// let y: Box<dyn Baz> = x.cast_box().unwrap();
+/// assert_eq!(y.baz(), -7);
+/// ```
+#[proc_macro_attribute]
+pub fn traitcast_trait(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let attr = proc_macro2::TokenStream::from(attr);
+        return syn::Error::new(
+            attr.span(),
+            "traitcast_trait does not take arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let mut item = parse_macro_input!(item as ItemTrait);
+    let present = item.supertraits.iter().any(|bound| match bound {
+        TypeParamBound::Trait(bound) => bound
+            .path
+            .segments
+            .last()
+            .is_some_and(|seg| seg.ident == "TraitcastFrom"),
+        _ => false,
+    });
+    if !present {
+        if item.colon_token.is_none() {
+            item.colon_token = Some(Default::default());
+        }
+        item.supertraits
+            .push(parse_quote!(::traitcast::TraitcastFrom));
+    }
+    quote!(#item).into()
+}
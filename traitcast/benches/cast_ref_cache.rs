@@ -0,0 +1,89 @@
+//! Compares `cast_ref` on homogeneous inputs, which hit the thread-local
+//! cache, with alternating inputs, which miss it, and with casting through a
+//! `Registry` directly, which has no cache. Run with `cargo bench`.
+
+use std::any::Any;
+use std::hint::black_box;
+use std::time::Instant;
+
+use traitcast::{impl_entry, traitcast, Registry};
+
+trait Shape: traitcast::TraitcastFrom {
+    fn area(&self) -> f64;
+}
+
+struct Square(f64);
+struct Circle(f64);
+
+impl Shape for Square {
+    fn area(&self) -> f64 {
+        self.0 * self.0
+    }
+}
+
+impl Shape for Circle {
+    fn area(&self) -> f64 {
+        3.0 * self.0 * self.0
+    }
+}
+
+traitcast!(struct Square: Shape);
+traitcast!(struct Circle: Shape);
+
+const ITERS: usize = 10_000_000;
+
+fn time(name: &str, mut f: impl FnMut() -> f64) {
+    let start = Instant::now();
+    let mut total = 0.0;
+    for _ in 0..ITERS {
+        total += f();
+    }
+    let elapsed = start.elapsed();
+    black_box(total);
+    println!(
+        "{:<24} {:>8.2} ns/cast",
+        name,
+        elapsed.as_nanos() as f64 / ITERS as f64
+    );
+}
+
+fn main() {
+    let mut registry = Registry::new();
+    registry.insert::<dyn Shape>(
+        vec![
+            impl_entry!(dyn Shape, Square),
+            impl_entry!(dyn Shape, Circle),
+        ]
+        .into_iter()
+        .collect(),
+    );
+
+    let squares: Vec<Box<dyn Any>> =
+        vec![Box::new(Square(2.0)), Box::new(Square(3.0))];
+    let mixed: Vec<Box<dyn Any>> =
+        vec![Box::new(Square(2.0)), Box::new(Circle(1.0))];
+
+    let mut i = 0;
+    time("cast_ref, homogeneous", || {
+        i += 1;
+        let x = &squares[i % squares.len()];
+        let x: &dyn Shape = traitcast::cast_ref(black_box(&**x)).unwrap();
+        x.area()
+    });
+
+    let mut i = 0;
+    time("cast_ref, alternating", || {
+        i += 1;
+        let x = &mixed[i % mixed.len()];
+        let x: &dyn Shape = traitcast::cast_ref(black_box(&**x)).unwrap();
+        x.area()
+    });
+
+    let mut i = 0;
+    time("Registry, homogeneous", || {
+        i += 1;
+        let x = &squares[i % squares.len()];
+        let x = registry.try_cast_ref::<_, dyn Shape>(black_box(&**x));
+        x.unwrap().area()
+    });
+}
@@ -0,0 +1,102 @@
+//! Measures the per-type lookup in a table with a few dozen impls, which is
+//! dominated by hashing the `TypeId`. Run with `cargo bench`, and again with
+//! `--features fast_hash` to compare the hashers.
+
+use std::any::Any;
+use std::hint::black_box;
+use std::time::Instant;
+
+use traitcast::{impl_entry, Registry};
+
+trait Shape: traitcast::TraitcastFrom {
+    fn sides(&self) -> usize;
+}
+
+macro_rules! shapes {
+    ($($name:ident = $sides:expr),+ $(,)?) => {
+        $(
+            struct $name;
+
+            impl Shape for $name {
+                fn sides(&self) -> usize {
+                    $sides
+                }
+            }
+        )+
+
+        fn make_registry() -> Registry {
+            let mut registry = Registry::new();
+            registry.insert::<dyn Shape>(
+                vec![$(impl_entry!(dyn Shape, $name)),+].into_iter().collect(),
+            );
+            registry
+        }
+
+        fn make_items() -> Vec<Box<dyn Any>> {
+            vec![$(Box::new($name)),+]
+        }
+    };
+}
+
+shapes!(
+    S0 = 0,
+    S1 = 1,
+    S2 = 2,
+    S3 = 3,
+    S4 = 4,
+    S5 = 5,
+    S6 = 6,
+    S7 = 7,
+    S8 = 8,
+    S9 = 9,
+    S10 = 10,
+    S11 = 11,
+    S12 = 12,
+    S13 = 13,
+    S14 = 14,
+    S15 = 15,
+    S16 = 16,
+    S17 = 17,
+    S18 = 18,
+    S19 = 19,
+    S20 = 20,
+    S21 = 21,
+    S22 = 22,
+    S23 = 23,
+    S24 = 24,
+    S25 = 25,
+    S26 = 26,
+    S27 = 27,
+    S28 = 28,
+    S29 = 29,
+    S30 = 30,
+    S31 = 31,
+);
+
+const ITERS: usize = 10_000_000;
+
+fn main() {
+    let registry = make_registry();
+    let items = make_items();
+    let table = registry.table::<dyn Shape>().unwrap();
+
+    let start = Instant::now();
+    let mut total = 0;
+    for i in 0..ITERS {
+        let x = &items[i % items.len()];
+        total += table.from_ref(black_box(&**x)).unwrap().sides();
+    }
+    let elapsed = start.elapsed();
+    black_box(total);
+
+    println!(
+        "{} impls, fast_hash {}: {:.2} ns/cast",
+        items.len(),
+        if cfg!(feature = "fast_hash") {
+            "on"
+        } else {
+            "off"
+        },
+        elapsed.as_nanos() as f64 / ITERS as f64
+    );
+}
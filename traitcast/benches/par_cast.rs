@@ -0,0 +1,74 @@
+//! Compares casting a large slice with `par_cast_refs` against casting it
+//! sequentially. Run with `cargo bench --features rayon`.
+
+use std::any::Any;
+use std::hint::black_box;
+use std::time::Instant;
+
+use traitcast::traitcast;
+
+trait Shape: traitcast::TraitcastFrom + Sync {
+    fn area(&self) -> f64;
+}
+
+struct Square(f64);
+struct Circle;
+
+impl Shape for Square {
+    fn area(&self) -> f64 {
+        self.0 * self.0
+    }
+}
+
+traitcast!(struct Square: Shape);
+traitcast!(struct Circle);
+
+const ITEMS: usize = 4_000_000;
+const ROUNDS: usize = 5;
+
+fn time(name: &str, mut f: impl FnMut() -> f64) {
+    let start = Instant::now();
+    let mut total = 0.0;
+    for _ in 0..ROUNDS {
+        total += f();
+    }
+    let elapsed = start.elapsed();
+    black_box(total);
+    println!(
+        "{:<12} {:>8.2} ns/cast",
+        name,
+        elapsed.as_nanos() as f64 / (ROUNDS * ITEMS) as f64
+    );
+}
+
+fn main() {
+    let values: Vec<Box<dyn Any + Sync>> = (0..ITEMS)
+        .map(|i| -> Box<dyn Any + Sync> {
+            if i % 3 == 0 {
+                Box::new(Circle)
+            } else {
+                Box::new(Square(i as f64))
+            }
+        })
+        .collect();
+    let items: Vec<&(dyn Any + Sync)> = values.iter().map(|x| &**x).collect();
+
+    time("sequential", || {
+        items
+            .iter()
+            .filter_map(|&x| {
+                let x: &dyn Any = x;
+                traitcast::cast_ref::<_, dyn Shape>(x)
+            })
+            .map(|x| x.area())
+            .sum()
+    });
+
+    time("parallel", || {
+        traitcast::par_cast_refs::<dyn Shape>(black_box(&items))
+            .into_iter()
+            .flatten()
+            .map(|x| x.area())
+            .sum()
+    });
+}
@@ -0,0 +1,155 @@
+//! Compares finding an entry by hashing its `TypeId` with scanning the table
+//! for it, and with binary searching a sorted `Vec` of the entries, for tables
+//! of 2, 8 and 64 impls. `from_ref` scans tables of up to
+//! `SMALL_TABLE_CUTOFF` impls and hashes otherwise. Run with `cargo bench`.
+
+use std::any::{Any, TypeId};
+use std::hint::black_box;
+use std::time::Instant;
+
+use traitcast::{impl_entry, ImplEntry};
+use traitcast_core::{CastIntoTrait, SMALL_TABLE_CUTOFF};
+
+trait Shape: traitcast::TraitcastFrom {
+    fn sides(&self) -> usize;
+}
+
+macro_rules! shapes {
+    ($($name:ident = $sides:expr),+ $(,)?) => {
+        $(
+            struct $name;
+
+            impl Shape for $name {
+                fn sides(&self) -> usize {
+                    $sides
+                }
+            }
+        )+
+
+        fn make_entries() -> Vec<(ImplEntry<dyn Shape>, Box<dyn Any>)> {
+            vec![$((impl_entry!(dyn Shape, $name), Box::new($name))),+]
+        }
+    };
+}
+
+shapes!(
+    S0 = 0,
+    S1 = 1,
+    S2 = 2,
+    S3 = 3,
+    S4 = 4,
+    S5 = 5,
+    S6 = 6,
+    S7 = 7,
+    S8 = 8,
+    S9 = 9,
+    S10 = 10,
+    S11 = 11,
+    S12 = 12,
+    S13 = 13,
+    S14 = 14,
+    S15 = 15,
+    S16 = 16,
+    S17 = 17,
+    S18 = 18,
+    S19 = 19,
+    S20 = 20,
+    S21 = 21,
+    S22 = 22,
+    S23 = 23,
+    S24 = 24,
+    S25 = 25,
+    S26 = 26,
+    S27 = 27,
+    S28 = 28,
+    S29 = 29,
+    S30 = 30,
+    S31 = 31,
+    S32 = 32,
+    S33 = 33,
+    S34 = 34,
+    S35 = 35,
+    S36 = 36,
+    S37 = 37,
+    S38 = 38,
+    S39 = 39,
+    S40 = 40,
+    S41 = 41,
+    S42 = 42,
+    S43 = 43,
+    S44 = 44,
+    S45 = 45,
+    S46 = 46,
+    S47 = 47,
+    S48 = 48,
+    S49 = 49,
+    S50 = 50,
+    S51 = 51,
+    S52 = 52,
+    S53 = 53,
+    S54 = 54,
+    S55 = 55,
+    S56 = 56,
+    S57 = 57,
+    S58 = 58,
+    S59 = 59,
+    S60 = 60,
+    S61 = 61,
+    S62 = 62,
+    S63 = 63,
+);
+
+const ITERS: usize = 10_000_000;
+
+fn time(name: &str, n: usize, mut f: impl FnMut(usize) -> usize) {
+    let start = Instant::now();
+    let mut total = 0;
+    for i in 0..ITERS {
+        total += f(i % n);
+    }
+    let elapsed = start.elapsed();
+    black_box(total);
+    println!(
+        "{:<10} N={:<3} {:>8.2} ns/lookup",
+        name,
+        n,
+        elapsed.as_nanos() as f64 / ITERS as f64
+    );
+}
+
+fn main() {
+    println!("SMALL_TABLE_CUTOFF = {}", SMALL_TABLE_CUTOFF);
+    for &n in &[2, 8, 64] {
+        let (entries, items): (Vec<_>, Vec<_>) =
+            make_entries().into_iter().take(n).unzip();
+        let table: CastIntoTrait<dyn Shape> = entries.into_iter().collect();
+        let tids: Vec<TypeId> = items.iter().map(|x| (**x).type_id()).collect();
+
+        time("hash", n, |i| {
+            let entry = &table.map[black_box(&tids[i])];
+            (entry.cast_ref)(&*items[i]).unwrap().sides()
+        });
+
+        time("scan", n, |i| {
+            let tid = black_box(tids[i]);
+            let (_, entry) =
+                table.map.iter().find(|(k, _)| **k == tid).unwrap();
+            (entry.cast_ref)(&*items[i]).unwrap().sides()
+        });
+
+        // The storage the table would use if it kept small tables in a
+        // sorted `Vec` instead of the map
+        let mut sorted: Vec<(TypeId, &ImplEntry<dyn Shape>)> =
+            table.map.iter().map(|(&tid, entry)| (tid, entry)).collect();
+        sorted.sort_unstable_by_key(|&(tid, _)| tid);
+        time("sorted_vec", n, |i| {
+            let tid = black_box(tids[i]);
+            let at = sorted.binary_search_by_key(&tid, |&(k, _)| k).unwrap();
+            (sorted[at].1.cast_ref)(&*items[i]).unwrap().sides()
+        });
+
+        time("from_ref", n, |i| {
+            table.from_ref(black_box(&*items[i])).unwrap().sides()
+        });
+    }
+}
@@ -0,0 +1,90 @@
+//! Compares casting through a precomputed `StaticDispatch` table with casting
+//! through the global registry. Run with `cargo bench`.
+
+use std::any::Any;
+use std::hint::black_box;
+use std::time::Instant;
+
+use traitcast::{impl_entry, traitcast, Registry, StaticDispatch};
+
+trait Shape: traitcast::TraitcastFrom {
+    fn area(&self) -> f64;
+}
+
+struct Square(f64);
+struct Circle(f64);
+
+impl Shape for Square {
+    fn area(&self) -> f64 {
+        self.0 * self.0
+    }
+}
+
+impl Shape for Circle {
+    fn area(&self) -> f64 {
+        3.0 * self.0 * self.0
+    }
+}
+
+traitcast!(struct Square: Shape);
+traitcast!(struct Circle: Shape);
+
+enum Kind {
+    Square,
+    Circle,
+}
+
+const ITERS: usize = 10_000_000;
+
+fn time(name: &str, mut f: impl FnMut() -> f64) {
+    let start = Instant::now();
+    let mut total = 0.0;
+    for _ in 0..ITERS {
+        total += f();
+    }
+    let elapsed = start.elapsed();
+    black_box(total);
+    println!(
+        "{:<16} {:>8.2} ns/cast",
+        name,
+        elapsed.as_nanos() as f64 / ITERS as f64
+    );
+}
+
+fn main() {
+    let mut registry = Registry::new();
+    registry.insert::<dyn Shape>(
+        vec![
+            impl_entry!(dyn Shape, Square),
+            impl_entry!(dyn Shape, Circle),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let dispatch = StaticDispatch::builder()
+        .add::<Square, dyn Shape>()
+        .add::<Circle, dyn Shape>()
+        .build(&registry)
+        .unwrap();
+
+    let items: Vec<(usize, Box<dyn Any>)> = vec![
+        (Kind::Square as usize, Box::new(Square(2.0))),
+        (Kind::Circle as usize, Box::new(Circle(1.0))),
+    ];
+
+    let mut i = 0;
+    time("cast_ref", || {
+        i += 1;
+        let (_, x) = &items[i % items.len()];
+        let x: &dyn Shape = traitcast::cast_ref(black_box(&**x)).unwrap();
+        x.area()
+    });
+
+    let mut i = 0;
+    time("StaticDispatch", || {
+        i += 1;
+        let (idx, x) = &items[i % items.len()];
+        let x = dispatch.dispatch::<dyn Shape, _>(*idx, black_box(&**x));
+        x.unwrap().area()
+    });
+}
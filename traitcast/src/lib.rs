@@ -121,29 +121,321 @@ fn main() {
 }
 ```
 
+Registering an impl which does not exist fails to compile, with an error
+pointing at the missing `Foo` impl for `A`.
+
+```compile_fail,E0277
+trait Foo: traitcast::TraitcastFrom {}
+
+struct A;
+
+traitcast::traitcast!(impl Foo; for A);
+```
+
 */
 
 #[cfg(test)]
 pub mod tests;
+#[cfg(feature = "typetag_compat")]
+pub mod typetag;
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Weak};
+#[cfg(feature = "global_mutable")]
+use std::sync::{RwLock, RwLockReadGuard};
+
+use once_cell::sync::{Lazy, OnceCell};
+
+pub use traitcast_core::builder::RegistryBuilder;
+pub use traitcast_core::clone::CastClone;
+use traitcast_core::inventory::build_registry_with;
+pub use traitcast_core::inventory::RegistrationHook;
+pub use traitcast_core::opcode::OpcodeTable;
+pub use traitcast_core::static_dispatch::StaticDispatch;
+pub use traitcast_core::visitor::TypeVisitor;
+use traitcast_core::CastIntoTrait;
+use traitcast_core::{
+    identity_box, identity_mut, identity_ref, trace_cast_failure,
+};
+pub use traitcast_core::{
+    impl_entry, register_std_traits, ImplEntry, RegisterSummary, Registry,
+};
+pub use traitcast_core::{
+    register_impl, register_trait, traitcast, traitcast_cfg_impl,
+    traitcast_enum_dispatch,
+};
+pub use traitcast_core::{
+    BoxCastError, CastError, CastExt, TraitTable, TraitcastError,
+    TraitcastFrom, TraitcastFromSend, TraitcastRegistered,
+};
+#[cfg(feature = "derive")]
+pub use traitcast_derive::{traitcast_trait, Traitcastable};
+
+/// This is a global table of all the trait objects that can be cast into.
+/// Each entry is a CastIntoTrait, i.e. a table of the implementations of a
+/// castable trait.
+#[cfg(not(feature = "global_mutable"))]
+static GLOBAL_REGISTRY: Lazy<Registry> = Lazy::new(build_global);
+
+/// With the `global_mutable` feature, the global registry sits behind a lock
+/// so that impls can be added to it at runtime.
+#[cfg(feature = "global_mutable")]
+static GLOBAL_REGISTRY: Lazy<RwLock<Registry>> =
+    Lazy::new(|| RwLock::new(build_global()));
+
+/// The hook installed by `set_registration_hook`, if any.
+static REGISTRATION_HOOK: OnceCell<RegistrationHook> = OnceCell::new();
+
+/// Builds a registry from `inventory`, through the registration hook if one
+/// is installed.
+fn build_global() -> Registry {
+    match REGISTRATION_HOOK.get() {
+        Some(&hook) => build_registry_with(hook),
+        None => build_registry_with(|_, _| true),
+    }
+}
+
+/// Installs a hook which is called for every entry as the global registry is
+/// built, with the entry's `from_name` and `into_name`. Returning `false`
+/// vetoes the entry, so that it is left out of the global registry. This is
+/// useful for logging or auditing every impl the binary linked in.
+///
+/// The global registry is built on first use, so the hook should be
+/// installed at the start of `main`. Fails, handing the hook back, if a hook
+/// is already installed or the global registry has already been built. With
+/// the `global_mutable` feature, `refresh_global_registry` runs the hook too.
+pub fn set_registration_hook(
+    hook: RegistrationHook,
+) -> Result<(), RegistrationHook> {
+    if Lazy::get(&GLOBAL_REGISTRY).is_some() {
+        return Err(hook);
+    }
+    REGISTRATION_HOOK.set(hook)
+}
+
+/// Borrows the global registry.
+#[cfg(not(feature = "global_mutable"))]
+fn global() -> &'static Registry {
+    &GLOBAL_REGISTRY
+}
+
+/// Borrows the global registry, holding a read lock until the guard is
+/// dropped.
+#[cfg(feature = "global_mutable")]
+fn global() -> RwLockReadGuard<'static, Registry> {
+    GLOBAL_REGISTRY.read().unwrap()
+}
+
+thread_local! {
+    /// A stack of registries which override the global registry on the
+    /// current thread. The most recently pushed registry is consulted first.
+    static LOCAL_REGISTRIES: RefCell<Vec<Registry>> =
+        const { RefCell::new(Vec::new()) };
+
+    /// The impl used by the last successful `cast_ref` on the current thread,
+    /// so that casting the same concrete type into the same trait again can
+    /// skip the registry. Cleared whenever the override stack changes.
+    static LAST_CAST_REF: RefCell<Option<CachedCastRef>> =
+        const { RefCell::new(None) };
+}
+
+/// The `cast_ref` function of an `ImplEntry<To>`.
+type CastRefFn<To> = fn(&dyn Any) -> Option<&To>;
+
+/// A type-erased `CastRefFn<To>` for the concrete type `from`. Downcasting it
+/// checks that it is for the right `To`.
+struct CachedCastRef {
+    from: TypeId,
+    cast_ref: Box<dyn Any>,
+}
+
+/// Gets the cached `cast_ref` function for casting from the concrete type
+/// `from` into `To`, if it is the last one that was used.
+fn cached_cast_ref<To>(from: TypeId) -> Option<CastRefFn<To>>
+where
+    To: ?Sized + 'static,
+{
+    LAST_CAST_REF.with(|last| {
+        let last = last.borrow();
+        let last = last.as_ref().filter(|last| last.from == from)?;
+        last.cast_ref.downcast_ref::<CastRefFn<To>>().copied()
+    })
+}
+
+/// Caches the `cast_ref` function used for casting from the concrete type
+/// `from` into `To`, replacing the previous one.
+fn cache_cast_ref<To>(from: TypeId, cast_ref: CastRefFn<To>)
+where
+    To: ?Sized + 'static,
+{
+    LAST_CAST_REF.with(|last| {
+        let mut last = last.borrow_mut();
+
+        // Reuse the allocation if the last cast was into the same trait
+        if let Some(last) = last.as_mut() {
+            if let Some(slot) = last.cast_ref.downcast_mut::<CastRefFn<To>>() {
+                *slot = cast_ref;
+                last.from = from;
+                return;
+            }
+        }
+
+        *last = Some(CachedCastRef {
+            from,
+            cast_ref: Box::new(cast_ref),
+        });
+    })
+}
 
-use std::any::Any;
+fn clear_cast_ref_cache() {
+    LAST_CAST_REF.with(|last| *last.borrow_mut() = None);
+}
+
+/// Pushes a registry onto the current thread's override stack. Until it is
+/// popped again, the top-level cast functions on this thread look up each
+/// target trait in the pushed registries, most recent first, before falling
+/// back to the global registry.
+///
+/// This is useful for test setups which need to control exactly which impls
+/// are visible, but which cannot wrap everything in a single closure.
+pub fn push_registry(registry: Registry) {
+    clear_cast_ref_cache();
+    LOCAL_REGISTRIES.with(|locals| locals.borrow_mut().push(registry));
+}
+
+/// Pops the most recently pushed registry off the current thread's override
+/// stack and returns it. Returns `None`, and does nothing, if the stack is
+/// empty.
+pub fn pop_registry() -> Option<Registry> {
+    clear_cast_ref_cache();
+    LOCAL_REGISTRIES.with(|locals| locals.borrow_mut().pop())
+}
+
+/// Calls `f` with the given registry pushed onto the current thread's
+/// override stack, as by `push_registry`, and pops it again afterwards. This
+/// lets each test control exactly which impls are visible to the top-level
+/// cast functions, without interfering with other tests.
+///
+/// The stack is restored even if `f` panics, so a failing test does not leak
+/// its registry into later tests on the same thread. Any registries `f`
+/// pushed without popping are removed as well.
+pub fn with_local_registry<R>(registry: Registry, f: impl FnOnce() -> R) -> R {
+    /// Truncates the override stack back to the given depth when dropped.
+    struct Restore(usize);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            clear_cast_ref_cache();
+            LOCAL_REGISTRIES
+                .with(|locals| locals.borrow_mut().truncate(self.0));
+        }
+    }
+
+    let _restore =
+        Restore(LOCAL_REGISTRIES.with(|locals| locals.borrow().len()));
+    push_registry(registry);
+    f()
+}
 
-pub use traitcast_core::TraitcastFrom;
-pub use traitcast_core::traitcast;
-use traitcast_core::inventory::build_registry;
-use traitcast_core::Registry;
+/// Gets the global registry, building it first if this is its first use. This
+/// is the registry of everything registered via `traitcast!`, and does not
+/// include any registries pushed with `push_registry`.
+#[cfg(not(feature = "global_mutable"))]
+pub fn global_registry() -> &'static Registry {
+    global()
+}
+
+/// Gets the global registry, building it first if this is its first use. This
+/// is the registry of everything registered via `traitcast!`, and does not
+/// include any registries pushed with `push_registry`.
+///
+/// With the `global_mutable` feature the registry can change, so rather than
+/// a `&'static Registry` this returns a guard holding a read lock on it until
+/// it is dropped. It must not be held while calling `register_global_impl` or
+/// `refresh_global_registry` on the same thread.
+#[cfg(feature = "global_mutable")]
+pub fn global_registry() -> RwLockReadGuard<'static, Registry> {
+    global()
+}
+
+/// Inserts a single entry into the global registry, overwriting any existing
+/// entry for the same pair. This is how impls are registered with a
+/// dynamically loaded library which does not use `inventory`.
+///
+/// Requires the `global_mutable` feature. That puts the global registry
+/// behind a `RwLock`, so every cast through it takes a read lock, and the
+/// iterator adaptors look up the target trait for each item rather than once.
+/// Without the feature the global registry is immutable and needs no locking.
+///
+/// Entries are never removed from the global registry, as casting through an
+/// entry after the library it points into has been closed is undefined
+/// behavior.
+#[cfg(feature = "global_mutable")]
+pub fn register_global_impl<To>(entry: ImplEntry<To>)
+where
+    To: ?Sized + 'static,
+{
+    GLOBAL_REGISTRY.write().unwrap().register_impl(entry);
+}
+
+/// Scans `inventory` again, and merges everything it finds into the global
+/// registry. Call this after loading a library with `dlopen` to pick up the
+/// library's `traitcast!` registrations. Entries added by
+/// `register_global_impl` are kept.
+///
+/// Requires the `global_mutable` feature; see `register_global_impl`.
+#[cfg(feature = "global_mutable")]
+pub fn refresh_global_registry() {
+    // Scan before taking the lock, so that casts are not blocked meanwhile
+    let fresh = build_global();
+    GLOBAL_REGISTRY.write().unwrap().merge(fresh);
+}
 
-lazy_static::lazy_static! {
-    /// This is a global table of all the trait objects that can be cast into.
-    /// Each entry is a CastIntoTrait, i.e. a table of the implementations of a
-    /// castable trait.
-    static ref GLOBAL_REGISTRY: Registry =
-        build_registry();
+/// Lists the names of the traits in the global registry, sorted. This only
+/// covers what was registered via `traitcast!`, not any registries pushed
+/// with `push_registry`.
+pub fn global_traits() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> =
+        global().registered_trait_names().collect();
+    names.sort_unstable();
+    names
+}
+
+/// Counts the impls in the global registry, across all traits. Like
+/// `global_traits`, this ignores registries pushed with `push_registry`.
+pub fn global_impl_count() -> usize {
+    global().audit().impl_count
+}
+
+/// Calls `f` with the table that the top-level cast functions should use to
+/// cast into `To` on the current thread, or `None` if no registry in scope
+/// knows about `To`.
+fn with_table<To, R>(f: impl FnOnce(Option<&CastIntoTrait<To>>) -> R) -> R
+where
+    To: ?Sized + 'static,
+{
+    LOCAL_REGISTRIES.with(|locals| {
+        let locals = locals.borrow();
+        let global = global();
+        let table = locals
+            .iter()
+            .rev()
+            .find_map(|registry| registry.cast_into::<To>())
+            .or_else(|| global.cast_into::<To>());
+        f(table)
+    })
 }
 
 /// A convenience trait with a blanket implementation that adds methods to cast
 /// from any trait that implements TraitcastFrom, to target with a static
 /// lifetime.
+///
+/// As with `cast_ref`, casting a trait object into its own trait is not the
+/// identity: the concrete type still has to be registered.
 pub trait Traitcast<To: ?Sized> {
     /// A convenience method that wraps the top-level `cast_ref` function.
     fn cast_ref(&self) -> Option<&To>;
@@ -153,6 +445,14 @@ pub trait Traitcast<To: ?Sized> {
 
     /// A convenience method that wraps the top-level `cast_box` function.
     fn cast_box(self: Box<Self>) -> Result<Box<To>, Box<dyn Any>>;
+
+    /// A convenience method that wraps the top-level `cast_arc` function.
+    fn cast_arc(self: Arc<Self>) -> Result<Arc<To>, Arc<dyn Any + Send + Sync>>
+    where
+        Self: TraitcastFromSend;
+
+    /// A convenience method that wraps the top-level `cast_rc` function.
+    fn cast_rc(self: Rc<Self>) -> Result<Rc<To>, Rc<dyn Any>>;
 }
 
 impl<From, To> Traitcast<To> for From
@@ -182,8 +482,74 @@ where
     fn cast_box(self: Box<Self>) -> Result<Box<To>, Box<dyn Any>> {
         cast_box(self)
     }
+
+    /// Tries to cast self to an atomically reference-counted dynamic trait
+    /// object. This will always return Err if the implementation of the
+    /// target trait, for the concrete type of self, has not been registered
+    /// via `traitcast!`.
+    fn cast_arc(self: Arc<Self>) -> Result<Arc<To>, Arc<dyn Any + Send + Sync>>
+    where
+        Self: TraitcastFromSend,
+    {
+        cast_arc(self)
+    }
+
+    /// Tries to cast self to a reference-counted dynamic trait object. This
+    /// will always return Err if the implementation of the target trait, for
+    /// the concrete type of self, has not been registered via `traitcast!`.
+    fn cast_rc(self: Rc<Self>) -> Result<Rc<To>, Rc<dyn Any>> {
+        cast_rc(self)
+    }
+}
+
+/// Adds methods to cast from any implementor of `TraitcastFrom` through the
+/// global registry, with the target as a parameter of the method rather than
+/// of the trait, so that `x.cast::<dyn Bar>()` works without naming
+/// `Traitcast<dyn Bar>`.
+///
+/// As with `CastExt`, `&self` methods called on a `Box<dyn Foo>` act on the
+/// box itself, so call them on `*x` rather than on the box.
+///
+/// # Example
+/// ```
+/// use traitcast::{traitcast, GlobalCastExt};
+///
+/// trait Foo: traitcast::TraitcastFrom {}
+/// trait Bar: traitcast::TraitcastFrom {
+///     fn bar(&mut self) -> i32;
+/// }
+///
+/// struct A(i32);
+/// impl Foo for A {}
+/// impl Bar for A {
+///     fn bar(&mut self) -> i32 {
+///         self.0 += 1;
+///         self.0
+///     }
+/// }
+///
+/// traitcast!(struct A: Foo, Bar);
+///
+/// let mut x: Box<dyn Foo> = Box::new(A(1));
+/// assert_eq!((*x).cast_as_mut::<dyn Bar>().unwrap().bar(), 2);
+/// assert!((*x).cast::<dyn Bar>().is_some());
+/// ```
+pub trait GlobalCastExt: TraitcastFrom {
+    /// Casts to a reference to the trait object `To`, like the top-level
+    /// `cast_ref` function.
+    fn cast<To: ?Sized + 'static>(&self) -> Option<&To> {
+        cast_ref(self)
+    }
+
+    /// Casts to a mutable reference to the trait object `To`, like the
+    /// top-level `cast_mut` function.
+    fn cast_as_mut<To: ?Sized + 'static>(&mut self) -> Option<&mut To> {
+        cast_mut(self)
+    }
 }
 
+impl<T: TraitcastFrom + ?Sized> GlobalCastExt for T {}
+
 /// Tests whether the given value is castable to some trait object. This will
 /// always return `false` if the implementation of the target trait, for the
 /// concrete type of x, has not been registered via `traitcast!`.
@@ -195,44 +561,848 @@ where
     cast_ref::<From, To>(x).is_some()
 }
 
+/// Tests whether the given reference is castable to some trait object, like
+/// `implements_trait`, but reads like a pattern test and infers the source
+/// type. `matches_trait!(x, dyn Foo)` is `implements_trait::<_, dyn Foo>(x)`.
+///
+/// As with `implements_trait`, `x` should be a reference to the value, so
+/// pass `&*x` rather than `&x` for a box.
+///
+/// # Example
+/// ```
+/// use traitcast::{matches_trait, traitcast, TraitcastFrom};
+///
+/// trait Foo: TraitcastFrom {}
+///
+/// struct A;
+/// impl Foo for A {}
+///
+/// traitcast!(struct A: Foo);
+///
+/// let x: &dyn std::any::Any = &A;
+/// if matches_trait!(x, dyn Foo) {
+///     println!("x is a Foo");
+/// }
+/// ```
+#[macro_export]
+macro_rules! matches_trait {
+    ($x:expr, $to:ty $(,)?) => {
+        $crate::implements_trait::<_, $to>($x)
+    };
+}
+
+/// Casts a service, e.g. one looked up in a dependency injection container,
+/// into a capability trait. Returns `None` if the service's type does not
+/// implement `To`, or if `To` is not registered at all, so unlike `cast_ref`
+/// this never panics.
+///
+/// Containers like `anymap` are keyed by concrete type, so the lookup itself
+/// yields a `&Service`, and this decides what the service can do.
+///
+/// # Example
+/// ```
+/// use traitcast::{cast_service, traitcast, TraitcastFrom};
+///
+/// trait HealthCheck: TraitcastFrom {
+///     fn healthy(&self) -> bool;
+/// }
+///
+/// struct Database {
+///     connected: bool,
+/// }
+///
+/// impl HealthCheck for Database {
+///     fn healthy(&self) -> bool {
+///         self.connected
+///     }
+/// }
+///
+/// traitcast!(struct Database: HealthCheck);
+///
+/// let mut services = anymap::AnyMap::new();
+/// services.insert(Database { connected: true });
+/// services.insert(String::from("not a service with checks"));
+///
+/// let db = services.get::<Database>().unwrap();
+/// let check: &dyn HealthCheck = cast_service(db).unwrap();
+/// assert!(check.healthy());
+///
+/// let name = services.get::<String>().unwrap();
+/// assert!(cast_service::<_, dyn HealthCheck>(name).is_none());
+/// ```
+pub fn cast_service<Service, To>(service: &Service) -> Option<&To>
+where
+    Service: 'static,
+    To: ?Sized + 'static,
+{
+    try_cast_ref(service).ok().flatten()
+}
+
+/// Tests whether casting into the given trait is possible at all, i.e. that
+/// it has been registered via `traitcast!` for at least one type. Unlike the
+/// cast functions, this never panics.
+pub fn is_trait_registered<To>() -> bool
+where
+    To: ?Sized + 'static,
+{
+    with_table::<To, _>(|table| table.is_some())
+}
+
+/// Tests whether the concrete type with `TypeId` `from` can be cast into the
+/// trait object type with `TypeId` `into`, without needing a value. Unlike
+/// the cast functions, this never panics.
+pub fn can_cast_types(from: TypeId, into: TypeId) -> bool {
+    LOCAL_REGISTRIES.with(|locals| {
+        let locals = locals.borrow();
+        match locals
+            .iter()
+            .rev()
+            .find(|registry| registry.has_trait(into))
+        {
+            Some(registry) => registry.can_cast(from, into),
+            None => global().can_cast(from, into),
+        }
+    })
+}
+
+/// Tests whether the given value is castable to every trait object type in
+/// `traits`, given by `TypeId`, as with `can_cast_types`. This serves
+/// data-driven capability checks, where the traits are not known statically.
+/// Traits which have not been registered count as not implemented, so this
+/// never panics. It is `true` for an empty list.
+pub fn implements_all<From>(x: &From, traits: &[TypeId]) -> bool
+where
+    From: TraitcastFrom + ?Sized,
+{
+    let from = (*x.as_any_ref()).type_id();
+    traits.iter().all(|&into| can_cast_types(from, into))
+}
+
+/// Like `implements_all`, but tests whether the value is castable to at least
+/// one of the trait object types. It is `false` for an empty list.
+pub fn implements_any<From>(x: &From, traits: &[TypeId]) -> bool
+where
+    From: TraitcastFrom + ?Sized,
+{
+    let from = (*x.as_any_ref()).type_id();
+    traits.iter().any(|&into| can_cast_types(from, into))
+}
+
+/// Erases the given pointer back to `Box<dyn Any>`, e.g. to store a
+/// `Box<dyn Foo>` again after processing it. This always succeeds, and the
+/// result can be cast again, into any trait its concrete type implements.
+pub fn box_to_any<From>(x: Box<From>) -> Box<dyn Any>
+where
+    From: TraitcastFrom + ?Sized,
+{
+    x.as_any_box()
+}
+
 /// Tries to cast the given pointer to a dynamic trait object. This will always
 /// return Err if the implementation of the target trait, for the concrete type
 /// of x, has not been registered via `traitcast!`.
+///
+/// This holds even when casting a `Box<dyn Foo>` into `dyn Foo`; use
+/// `cast_box_or_identity` for a cast which always succeeds in that case.
+///
+/// Casting a box only replaces its vtable pointer, so it never allocates or
+/// moves the value, whether it succeeds or not. The result owns the same
+/// allocation that was passed in.
 pub fn cast_box<From, To>(x: Box<From>) -> Result<Box<To>, Box<dyn Any>>
 where
     From: TraitcastFrom + ?Sized,
     To: ?Sized + 'static,
 {
-    GLOBAL_REGISTRY
-        .cast_into::<To>()
+    try_cast_box(x)
         .expect("Calling cast_box to cast into an unregistered trait object")
-        .from_box(x)
+}
+
+/// Like `cast_box`, but fails with a `BoxCastError` naming the source and
+/// target types, rather than the bare box. The box can be recovered with
+/// `BoxCastError::into_inner`. This also fails, rather than panicking, if the
+/// target trait has not been registered at all.
+pub fn cast_box_checked<From, To>(x: Box<From>) -> Result<Box<To>, BoxCastError>
+where
+    From: TraitcastFrom + ?Sized,
+    To: ?Sized + 'static,
+{
+    let error = |original| BoxCastError {
+        original,
+        from_name: std::any::type_name::<From>(),
+        into_name: std::any::type_name::<To>(),
+    };
+    let x = match identity_box::<dyn Any, To>(x.as_any_box()) {
+        Ok(x) => return Ok(x),
+        Err(x) => x,
+    };
+    with_table(move |table: Option<&CastIntoTrait<To>>| {
+        match table.ok_or_else(not_registered::<To>) {
+            Ok(table) => table.from_box(x).map_err(error),
+            Err(_) => Err(error(x)),
+        }
+    })
+}
+
+/// Clones the value behind the given reference into a new box, and tries to
+/// cast that to a boxed dynamic trait object. This serves APIs which only
+/// have a borrow but need an owned cast result.
+///
+/// Returns `None` unless the concrete type of x has been registered both as
+/// `CastClone`, e.g. with `traitcast!(impl CastClone; for A)`, and for the
+/// target trait.
+pub fn cast_clone_box<From, To>(x: &From) -> Option<Box<To>>
+where
+    From: TraitcastFrom + ?Sized,
+    To: ?Sized + 'static,
+{
+    let x: &dyn CastClone = try_cast_ref(x).ok()??;
+    try_cast_box(x.clone_any()).ok()?.ok()
+}
+
+/// Tries to cast every box in the given vector to a boxed dynamic trait
+/// object, and partitions the results into the boxes that were cast and the
+/// ones that couldn't be, each in their original order. A box is only cast
+/// if the implementation of the target trait, for its concrete type, has
+/// been registered via `traitcast!`.
+///
+/// The boxes are reused, so the only allocations are for the output vectors.
+/// The target trait is only looked up once for the whole vector.
+pub fn cast_box_vec<From, To>(
+    xs: Vec<Box<From>>,
+) -> (Vec<Box<To>>, Vec<Box<dyn Any>>)
+where
+    From: TraitcastFrom + ?Sized,
+    To: ?Sized + 'static,
+{
+    with_table(move |table| {
+        let table = table.expect(
+            "Calling cast_box_vec to cast into an unregistered trait object",
+        );
+        let mut cast = Vec::new();
+        let mut failed = Vec::new();
+        for x in xs {
+            match table.from_box(x) {
+                Ok(x) => cast.push(x),
+                Err(x) => failed.push(x),
+            }
+        }
+        (cast, failed)
+    })
+}
+
+/// Tries to cast the given pinned box to a pinned dynamic trait object. This
+/// will always return Err if the implementation of the target trait, for the
+/// concrete type of x, has not been registered via `traitcast!`.
+///
+/// The pointee stays pinned throughout, so this is fine for `!Unpin` types.
+pub fn cast_pin_box<From, To>(
+    x: Pin<Box<From>>,
+) -> Result<Pin<Box<To>>, Pin<Box<dyn Any>>>
+where
+    From: TraitcastFrom + ?Sized,
+    To: ?Sized + 'static,
+{
+    with_table(move |table| {
+        table
+            .expect(
+                "Calling cast_pin_box to cast into an unregistered trait object",
+            )
+            .from_pin_box(x)
+    })
+}
+
+/// Like `cast_box`, but for `Send + Sync` boxes, which keep their bounds on
+/// failure. To keep them on success too, cast into a trait object type with
+/// the bounds, registered e.g. with `traitcast!(A => dyn Foo + Send + Sync)`.
+pub fn cast_box_send<From, To>(
+    x: Box<From>,
+) -> Result<Box<To>, Box<dyn Any + Send + Sync>>
+where
+    From: TraitcastFromSend + ?Sized,
+    To: ?Sized + 'static,
+{
+    with_table(move |table| {
+        table
+            .expect(
+                "Calling cast_box_send to cast into an unregistered trait \
+                 object",
+            )
+            .from_box_send(x)
+    })
+}
+
+/// Tries to cast the given atomically reference-counted pointer to a dynamic
+/// trait object. This will always return Err if the implementation of the
+/// target trait, for the concrete type of x, has not been registered via
+/// `traitcast!`.
+///
+/// Only `Send + Sync` types can be cast this way. The pointer is never cloned,
+/// so on failure the original `Arc` is returned with its strong count
+/// unchanged, and can be recovered with `Arc::downcast`.
+pub fn cast_arc<From, To>(
+    x: Arc<From>,
+) -> Result<Arc<To>, Arc<dyn Any + Send + Sync>>
+where
+    From: TraitcastFromSend + ?Sized,
+    To: ?Sized + 'static,
+{
+    with_table(move |table| {
+        table
+            .expect(
+                "Calling cast_arc to cast into an unregistered trait object",
+            )
+            .from_arc(x)
+    })
+}
+
+/// Upgrades the given weak pointer and tries to cast the result to a dynamic
+/// trait object. Returns `None` if the pointee has already been dropped, or
+/// if the implementation of the target trait, for the concrete type of the
+/// pointee, has not been registered via `traitcast!`.
+///
+/// If the upgrade succeeds but the cast fails, the upgraded `Arc` is dropped
+/// before returning, so the strong count is left as it was.
+pub fn cast_weak<From, To>(x: &Weak<From>) -> Option<Arc<To>>
+where
+    From: TraitcastFromSend + ?Sized,
+    To: ?Sized + 'static,
+{
+    cast_arc(x.upgrade()?).ok()
+}
+
+/// Tries to cast the given reference-counted pointer to a dynamic trait
+/// object. This will always return Err if the implementation of the target
+/// trait, for the concrete type of x, has not been registered via
+/// `traitcast!`.
+///
+/// On failure the original `Rc` is returned, so the caller keeps ownership.
+pub fn cast_rc<From, To>(x: Rc<From>) -> Result<Rc<To>, Rc<dyn Any>>
+where
+    From: TraitcastFrom + ?Sized,
+    To: ?Sized + 'static,
+{
+    with_table(move |table| {
+        table
+            .expect("Calling cast_rc to cast into an unregistered trait object")
+            .from_rc(x)
+    })
 }
 
 /// Tries to cast the given mutable reference to a dynamic trait object. This
 /// will always return None if the implementation of the target trait, for the
 /// concrete type of x, has not been registered via `traitcast!`.
-pub fn cast_mut<'a, From, To>(x: &'a mut From) -> Option<&'a mut To>
+///
+/// The result borrows `x` mutably, so only one trait object can be cast out
+/// of a value at a time. There is deliberately no way to cast into two traits
+/// at once: two `&mut` references to the same value are undefined behaviour
+/// even if the impls only touch disjoint fields, since each one claims
+/// exclusive access to the whole value. Cast into each trait in turn instead.
+pub fn cast_mut<From, To>(x: &mut From) -> Option<&mut To>
 where
     From: TraitcastFrom + ?Sized,
     To: ?Sized + 'static,
 {
-    GLOBAL_REGISTRY
-        .cast_into::<To>()
+    try_cast_mut(x)
         .expect("Calling cast_mut to cast into an unregistered trait object")
-        .from_mut(x)
 }
 
 /// Tries to cast the given reference to a dynamic trait object. This will
 /// always return None if the implementation of the target trait, for the
 /// concrete type of x, has not been registered via `traitcast!`.
-pub fn cast_ref<'a, From, To>(x: &'a From) -> Option<&'a To>
+///
+/// `dyn Any` is a valid target too, without being registered, so that generic
+/// code can re-erase a trait object like any other cast. The same goes for
+/// `cast_mut` and `cast_box`, and their `try_` variants.
+///
+/// Casting a trait object into its own trait, e.g. `dyn Foo` into `dyn Foo`,
+/// is not the identity either, and fails if the concrete type is not
+/// registered for `Foo`. Telling that `From` and `To` are the same type needs
+/// their `TypeId`s, which needs `From: 'static`. A `&dyn Foo` parameter with
+/// an elided lifetime is a `&(dyn Foo + '_)`, which is not `'static`, so
+/// `cast_ref` can't take that bound without rejecting such callers, and
+/// comparing the types without it would be unsound. Generic code which may
+/// cast a trait object into its own trait should use `cast_ref_or_identity`,
+/// or a `Registry` with `set_identity_fast_path`, which have the bound.
+pub fn cast_ref<From, To>(x: &From) -> Option<&To>
 where
     From: TraitcastFrom + ?Sized,
     To: ?Sized + 'static,
 {
-    GLOBAL_REGISTRY
-        .cast_into::<To>()
+    try_cast_ref(x)
         .expect("Calling cast_ref to cast into an unregistered trait object")
-        .from_ref(x)
+}
+
+/// One of two values, as returned by `cast_ref_either`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Tries to cast the given reference to one of two dynamic trait objects,
+/// trying `A` first. This suits dispatch where exactly one of two traits is
+/// expected. Returns `None` if the concrete type of x has neither impl.
+///
+/// As with `cast_ref`, this panics if a trait it needs to try has not been
+/// registered at all.
+pub fn cast_ref_either<From, A, B>(x: &From) -> Option<Either<&A, &B>>
+where
+    From: TraitcastFrom + ?Sized,
+    A: ?Sized + 'static,
+    B: ?Sized + 'static,
+{
+    match cast_ref::<From, A>(x) {
+        Some(a) => Some(Either::Left(a)),
+        None => cast_ref::<From, B>(x).map(Either::Right),
+    }
+}
+
+/// Like `cast_ref`, but extends the lifetime of the result to `'static`. This
+/// suits values known to live forever, such as leaked singletons, whose
+/// references have been shortened along the way, e.g. by an FFI boundary or a
+/// container handing out borrows.
+///
+/// # Safety
+/// The value behind x must stay alive, and must not be mutated or moved, for
+/// the rest of the program. A value leaked with `Box::leak` satisfies this as
+/// long as no `&mut` to it is used again.
+pub unsafe fn cast_ref_static<From, To>(x: &From) -> Option<&'static To>
+where
+    From: TraitcastFrom + ?Sized,
+    To: ?Sized + 'static,
+{
+    // Safety: guaranteed by the caller
+    cast_ref::<From, To>(x).map(|x| &*(x as *const To))
+}
+
+/// Like `cast_ref`, but always succeeds if `From` and `To` are the same type,
+/// whether or not the concrete type of x is registered. This suits generic
+/// code which may end up casting a trait object into its own trait.
+///
+/// See `cast_ref` for why it can't do this itself.
+pub fn cast_ref_or_identity<From, To>(x: &From) -> Option<&To>
+where
+    From: TraitcastFrom + ?Sized + 'static,
+    To: ?Sized + 'static,
+{
+    identity_ref(x).or_else(|| cast_ref(x))
+}
+
+/// Like `cast_mut`, but always succeeds if `From` and `To` are the same
+/// type. See `cast_ref_or_identity`.
+pub fn cast_mut_or_identity<From, To>(x: &mut From) -> Option<&mut To>
+where
+    From: TraitcastFrom + ?Sized + 'static,
+    To: ?Sized + 'static,
+{
+    match identity_mut(x) {
+        Ok(x) => Some(x),
+        Err(x) => cast_mut(x),
+    }
+}
+
+/// Like `cast_box`, but always succeeds if `From` and `To` are the same
+/// type. See `cast_ref_or_identity`.
+pub fn cast_box_or_identity<From, To>(
+    x: Box<From>,
+) -> Result<Box<To>, Box<dyn Any>>
+where
+    From: TraitcastFrom + ?Sized + 'static,
+    To: ?Sized + 'static,
+{
+    match identity_box(x) {
+        Ok(x) => Ok(x),
+        Err(x) => cast_box(x),
+    }
+}
+
+/// Like `cast_ref`, but casts the contents of a borrowed `Box<dyn Any>`,
+/// e.g. one stored in a container. Passing `&Box<dyn Any>` to `cast_ref`
+/// directly would cast the box itself, which always fails.
+#[allow(clippy::borrowed_box)]
+pub fn cast_ref_boxed<To>(x: &Box<dyn Any>) -> Option<&To>
+where
+    To: ?Sized + 'static,
+{
+    cast_ref::<dyn Any, To>(&**x)
+}
+
+/// Like `cast_mut`, but casts the contents of a mutably borrowed
+/// `Box<dyn Any>`.
+pub fn cast_mut_boxed<To>(x: &mut Box<dyn Any>) -> Option<&mut To>
+where
+    To: ?Sized + 'static,
+{
+    cast_mut::<dyn Any, To>(&mut **x)
+}
+
+/// Like `cast_box`, but returns an error instead of panicking if the target
+/// trait has not been registered at all. Within `Ok`, the result is `Err` if
+/// the trait is registered but the concrete type's impl is not.
+pub fn try_cast_box<From, To>(
+    x: Box<From>,
+) -> Result<Result<Box<To>, Box<dyn Any>>, CastError>
+where
+    From: TraitcastFrom + ?Sized,
+    To: ?Sized + 'static,
+{
+    let x = match identity_box::<dyn Any, To>(x.as_any_box()) {
+        Ok(x) => return Ok(Ok(x)),
+        Err(x) => x,
+    };
+
+    with_table(move |table| {
+        Ok(table.ok_or_else(not_registered::<To>)?.from_box(x))
+    })
+}
+
+/// Like `cast_mut`, but returns an error instead of panicking if the target
+/// trait has not been registered at all. `Ok(None)` means that the trait is
+/// registered but the concrete type's impl is not.
+pub fn try_cast_mut<From, To>(
+    x: &mut From,
+) -> Result<Option<&mut To>, CastError>
+where
+    From: TraitcastFrom + ?Sized,
+    To: ?Sized + 'static,
+{
+    let x = match identity_mut::<dyn Any, To>(x.as_any_mut()) {
+        Ok(x) => return Ok(Some(x)),
+        Err(x) => x,
+    };
+
+    with_table(move |table| {
+        Ok(table.ok_or_else(not_registered::<To>)?.from_mut(x))
+    })
+}
+
+/// Like `cast_ref`, but returns an error instead of panicking if the target
+/// trait has not been registered at all. `Ok(None)` means that the trait is
+/// registered but the concrete type's impl is not.
+///
+/// Each thread remembers the impl used by its last successful cast, so
+/// repeatedly casting the same concrete type into the same trait skips the
+/// registry lookups.
+pub fn try_cast_ref<From, To>(x: &From) -> Result<Option<&To>, CastError>
+where
+    From: TraitcastFrom + ?Sized,
+    To: ?Sized + 'static,
+{
+    let x = x.as_any_ref();
+    if let Some(x) = identity_ref::<dyn Any, To>(x) {
+        return Ok(Some(x));
+    }
+
+    let from = Any::type_id(x);
+    if let Some(cast_ref) = cached_cast_ref::<To>(from) {
+        return Ok(cast_ref(x));
+    }
+
+    with_table(move |table| {
+        let table = table.ok_or_else(not_registered::<To>)?;
+        let entry = match table.entry_or_trace(from) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        cache_cast_ref(from, entry.cast_ref);
+        Ok((entry.cast_ref)(x))
+    })
+}
+
+fn not_registered<To: ?Sized>() -> CastError {
+    let err = CastError::TraitNotRegistered {
+        into_name: std::any::type_name::<To>(),
+    };
+    trace_cast_failure(&err);
+    err
+}
+
+/// Tries to cast every element of the given slice to a dynamic trait object,
+/// and returns the index and cast result of each element that could be cast,
+/// in order.
+///
+/// The target trait is only looked up once for the whole slice.
+pub fn cast_indexed_refs<To>(items: &[Box<dyn Any>]) -> Vec<(usize, &To)>
+where
+    To: ?Sized + 'static,
+{
+    with_table(move |table| {
+        let table = table.expect(
+            "Calling cast_indexed_refs to cast into an unregistered trait \
+             object",
+        );
+        items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, x)| Some((i, table.from_ref(&**x)?)))
+            .collect()
+    })
+}
+
+/// Tries to cast the boxed trait object behind a guard, such as a
+/// `RwLockReadGuard` or a `MutexGuard`, to a dynamic trait object. The result
+/// borrows from the guard, so it can't outlive the lock being held. This will
+/// always return None if the implementation of the target trait, for the
+/// concrete type of the boxed value, has not been registered via
+/// `traitcast!`.
+pub fn cast_guard_ref<'a, G, From, To>(guard: &'a G) -> Option<&'a To>
+where
+    G: Deref<Target = Box<From>>,
+    From: TraitcastFrom + ?Sized + 'a,
+    To: ?Sized + 'static,
+{
+    cast_ref(&***guard)
+}
+
+/// Like `cast_guard_ref`, but for guards giving mutable access, such as a
+/// `RwLockWriteGuard`.
+pub fn cast_guard_mut<'a, G, From, To>(guard: &'a mut G) -> Option<&'a mut To>
+where
+    G: DerefMut<Target = Box<From>>,
+    From: TraitcastFrom + ?Sized + 'a,
+    To: ?Sized + 'static,
+{
+    cast_mut(&mut ***guard)
+}
+
+/// Tries to cast every element of the given slice to a dynamic trait object,
+/// returning the results in the same order. An element's result is `None` if
+/// the implementation of the target trait, for its concrete type, has not
+/// been registered via `traitcast!`.
+///
+/// The target trait is only looked up once for the whole slice.
+pub fn cast_slice_ref<To>(items: &[Box<dyn Any>]) -> Vec<Option<&To>>
+where
+    To: ?Sized + 'static,
+{
+    with_table(move |table| {
+        let table = table.expect(
+            "Calling cast_slice_ref to cast into an unregistered trait object",
+        );
+        items.iter().map(|x| table.from_ref(&**x)).collect()
+    })
+}
+
+/// Like `cast_slice_ref`, but casts the elements in parallel with `rayon`.
+/// Requires the `rayon` feature.
+///
+/// The target trait is looked up once, on the calling thread, so a registry
+/// pushed with `push_registry` is used even though the casts happen on other
+/// threads.
+#[cfg(feature = "rayon")]
+pub fn par_cast_refs<'a, To>(
+    items: &[&'a (dyn Any + Sync)],
+) -> Vec<Option<&'a To>>
+where
+    To: ?Sized + Sync + 'static,
+{
+    use rayon::prelude::*;
+
+    with_table(move |table| {
+        let table = table.expect(
+            "Calling par_cast_refs to cast into an unregistered trait object",
+        );
+        items
+            .par_iter()
+            .map(|&x| table.from_ref::<dyn Any>(x))
+            .collect()
+    })
+}
+
+/// The table an iterator adaptor casts with, which is resolved once when the
+/// adaptor is created.
+enum ResolvedTable<To: ?Sized + 'static> {
+    /// No pushed registry overrides `To`, so the global table can be held on
+    /// to. `None` if `To` is not registered at all.
+    #[cfg_attr(feature = "global_mutable", allow(dead_code))]
+    Global(Option<&'static CastIntoTrait<To>>),
+    /// A pushed registry overrides `To`, or the global registry is behind a
+    /// lock. Either way, the table can't be borrowed for the lifetime of the
+    /// adaptor, so this falls back to looking it up for each item.
+    Local,
+}
+
+impl<To: ?Sized + 'static> ResolvedTable<To> {
+    #[cfg(feature = "global_mutable")]
+    fn resolve() -> Self {
+        ResolvedTable::Local
+    }
+
+    #[cfg(not(feature = "global_mutable"))]
+    fn resolve() -> Self {
+        let overridden = LOCAL_REGISTRIES.with(|locals| {
+            locals
+                .borrow()
+                .iter()
+                .any(|registry| registry.is_trait_registered::<To>())
+        });
+        if overridden {
+            ResolvedTable::Local
+        } else {
+            ResolvedTable::Global(global().cast_into::<To>())
+        }
+    }
+
+    fn cast_ref<'a, From>(&self, x: &'a From) -> Option<&'a To>
+    where
+        From: TraitcastFrom + ?Sized,
+    {
+        match self {
+            ResolvedTable::Global(table) => table
+                .expect(
+                    "Calling cast_ref to cast into an unregistered trait object",
+                )
+                .from_ref(x),
+            ResolvedTable::Local => cast_ref(x),
+        }
+    }
+
+    fn cast_mut<'a, From>(&self, x: &'a mut From) -> Option<&'a mut To>
+    where
+        From: TraitcastFrom + ?Sized,
+    {
+        match self {
+            ResolvedTable::Global(table) => table
+                .expect(
+                    "Calling cast_mut to cast into an unregistered trait object",
+                )
+                .from_mut(x),
+            ResolvedTable::Local => cast_mut(x),
+        }
+    }
+}
+
+/// Extension methods for casting the items of iterators over references to
+/// castable objects. This is blanket implemented for all such iterators.
+///
+/// The target trait is looked up in the registries in scope when the adaptor
+/// is created, rather than once per item.
+pub trait TraitcastIterExt<'a, From>:
+    Iterator<Item = &'a From> + Sized
+where
+    From: TraitcastFrom + ?Sized + 'a,
+{
+    /// Casts each item into `To` with `cast_ref`, skipping the items that
+    /// can't be cast.
+    fn cast_filter<To>(self) -> CastFilter<Self, To>
+    where
+        To: ?Sized + 'static,
+    {
+        CastFilter {
+            iter: self,
+            table: ResolvedTable::resolve(),
+        }
+    }
+
+    /// Casts each item into `To` with `cast_ref`, and then applies `f` to the
+    /// items that could be cast, yielding the `Some` results like
+    /// `Iterator::filter_map`.
+    fn cast_filter_map<To, U, F>(self, f: F) -> CastFilterMap<Self, To, F>
+    where
+        To: ?Sized + 'static,
+        F: FnMut(&'a To) -> Option<U>,
+    {
+        CastFilterMap {
+            inner: self.cast_filter(),
+            f,
+        }
+    }
+}
+
+impl<'a, From, I> TraitcastIterExt<'a, From> for I
+where
+    I: Iterator<Item = &'a From>,
+    From: TraitcastFrom + ?Sized + 'a,
+{
+}
+
+/// Like `TraitcastIterExt`, but for iterators over mutable references.
+pub trait TraitcastIterMutExt<'a, From>:
+    Iterator<Item = &'a mut From> + Sized
+where
+    From: TraitcastFrom + ?Sized + 'a,
+{
+    /// Casts each item into `To` with `cast_mut`, skipping the items that
+    /// can't be cast.
+    fn cast_filter_mut<To>(self) -> CastFilterMut<Self, To>
+    where
+        To: ?Sized + 'static,
+    {
+        CastFilterMut {
+            iter: self,
+            table: ResolvedTable::resolve(),
+        }
+    }
+}
+
+impl<'a, From, I> TraitcastIterMutExt<'a, From> for I
+where
+    I: Iterator<Item = &'a mut From>,
+    From: TraitcastFrom + ?Sized + 'a,
+{
+}
+
+/// An iterator that casts the items of another iterator into `To`, skipping
+/// those that can't be cast. Created by `TraitcastIterExt::cast_filter`.
+pub struct CastFilter<I, To: ?Sized + 'static> {
+    iter: I,
+    table: ResolvedTable<To>,
+}
+
+impl<'a, From, I, To> Iterator for CastFilter<I, To>
+where
+    I: Iterator<Item = &'a From>,
+    From: TraitcastFrom + ?Sized + 'a,
+    To: ?Sized + 'static,
+{
+    type Item = &'a To;
+
+    fn next(&mut self) -> Option<&'a To> {
+        let table = &self.table;
+        self.iter.by_ref().find_map(|x| table.cast_ref(x))
+    }
+}
+
+/// An iterator that casts the items of another iterator into `To`, skipping
+/// those that can't be cast. Created by
+/// `TraitcastIterMutExt::cast_filter_mut`.
+pub struct CastFilterMut<I, To: ?Sized + 'static> {
+    iter: I,
+    table: ResolvedTable<To>,
+}
+
+impl<'a, From, I, To> Iterator for CastFilterMut<I, To>
+where
+    I: Iterator<Item = &'a mut From>,
+    From: TraitcastFrom + ?Sized + 'a,
+    To: ?Sized + 'static,
+{
+    type Item = &'a mut To;
+
+    fn next(&mut self) -> Option<&'a mut To> {
+        let table = &self.table;
+        self.iter.by_ref().find_map(|x| table.cast_mut(x))
+    }
+}
+
+/// An iterator that casts the items of another iterator into `To` and maps
+/// the results. Created by `TraitcastIterExt::cast_filter_map`.
+pub struct CastFilterMap<I, To: ?Sized + 'static, F> {
+    inner: CastFilter<I, To>,
+    f: F,
+}
+
+impl<'a, From, I, To, U, F> Iterator for CastFilterMap<I, To, F>
+where
+    I: Iterator<Item = &'a From>,
+    From: TraitcastFrom + ?Sized + 'a,
+    To: ?Sized + 'static,
+    F: FnMut(&'a To) -> Option<U>,
+{
+    type Item = U;
+
+    fn next(&mut self) -> Option<U> {
+        let f = &mut self.f;
+        self.inner.by_ref().find_map(f)
+    }
 }
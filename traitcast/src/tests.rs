@@ -14,12 +14,54 @@ mod traits {
     pub trait Baz: crate::TraitcastFrom {
         fn baz(self: Box<Self>) -> i64;
     }
+
+    /// Registered with `cfg(all())`, which always holds.
+    pub trait CfgAll: crate::TraitcastFrom {}
+
+    /// Registered with `cfg(any())`, which never holds.
+    pub trait CfgAny: crate::TraitcastFrom {}
+
+    /// Registered with `cfg` attributes forwarded by `traitcast!`.
+    pub trait CfgAttr: crate::TraitcastFrom {}
+
+    /// Implemented by `Split`, which is cast into this and `Right` in turn.
+    pub trait Left: crate::TraitcastFrom {
+        fn bump_left(&mut self) -> i64;
+    }
+
+    /// Implemented by `Split`, which is cast into this and `Left` in turn.
+    pub trait Right: crate::TraitcastFrom {
+        fn bump_right(&mut self) -> i64;
+    }
+
+    /// Registered for several types in one `traitcast!`.
+    pub trait Named: crate::TraitcastFrom {
+        fn name(&self) -> &'static str;
+    }
+
+    /// Registered along with `Named`.
+    pub trait Measured: crate::TraitcastFrom {
+        fn size(&self) -> usize;
+    }
+
+    /// `Send + Sync`, so that `Arc<dyn SendFoo>` and `Box<dyn SendFoo>` can be
+    /// cast from while keeping the bounds. Never cast into.
+    pub trait SendFoo: crate::TraitcastFromSend {}
+
+    /// Never registered globally, only in local registries.
+    pub trait Qux: crate::TraitcastFrom {
+        fn qux(&self) -> i64;
+    }
 }
 
 mod structs {
     use crate::traitcast;
 
-    use crate::tests::traits::{self, Bar, Foo};
+    use crate::tests::traits::{
+        self, Bar, CfgAll, CfgAny, CfgAttr, Foo, Left, Measured, Named, Qux,
+        Right, SendFoo,
+    };
+    #[derive(Clone)]
     pub struct A {
         pub x: i64,
     }
@@ -54,19 +96,180 @@ mod structs {
         }
     }
 
-    traitcast!(struct A: Foo, Bar);
-    traitcast!(struct B: Foo, traits::Baz);
+    impl Qux for A {
+        fn qux(&self) -> i64 {
+            -self.x
+        }
+    }
+
+    impl Qux for B {
+        fn qux(&self) -> i64 {
+            -self.y
+        }
+    }
+
+    impl SendFoo for A {}
+    impl SendFoo for B {}
+
+    impl CfgAll for A {}
+    impl CfgAll for B {}
+    impl CfgAny for A {}
+    impl CfgAny for B {}
+
+    crate::traitcast_cfg_impl!(cfg(all()) => (CfgAll, A); else => (CfgAll, B));
+    crate::traitcast_cfg_impl!(cfg(any()) => (CfgAny, A); else => (CfgAny, B));
+
+    impl CfgAttr for A {}
+    impl CfgAttr for B {}
+
+    traitcast!(#[cfg(all())] impl CfgAttr; for A);
+    traitcast!(#[cfg(any())] impl CfgAttr; for B);
+
+    /// What `#[enum_dispatch(Bar)]` generates for an enum over `A`.
+    pub enum Dispatch {
+        A(A),
+    }
+
+    impl Bar for Dispatch {
+        fn bar(&self) -> i64 {
+            match self {
+                Dispatch::A(a) => a.bar(),
+            }
+        }
+    }
+
+    crate::traitcast_enum_dispatch!(Dispatch: Bar);
+
+    /// Is `!Unpin`, like most hand-written futures.
+    pub struct Pinned {
+        pub x: i64,
+        pub _pin: std::marker::PhantomPinned,
+    }
+
+    impl Foo for Pinned {
+        fn foo(&mut self) -> i64 {
+            self.x
+        }
+    }
+
+    impl Bar for Pinned {
+        fn bar(&self) -> i64 {
+            self.x
+        }
+    }
+
+    traitcast!(struct Pinned: Foo, Bar);
+    // The plain struct form may be repeated, e.g. in another module
+    mod again {
+        use super::*;
+        traitcast!(struct Pinned: Foo);
+    }
+    // and used for foreign types
+    traitcast!(struct String: std::fmt::Display);
+
+    /// Two counters, each bumped through one of its traits.
+    pub struct Split {
+        pub left: i64,
+        pub right: i64,
+    }
+
+    impl Left for Split {
+        fn bump_left(&mut self) -> i64 {
+            self.left += 1;
+            self.left
+        }
+    }
+
+    impl Right for Split {
+        fn bump_right(&mut self) -> i64 {
+            self.right += 1;
+            self.right
+        }
+    }
+
+    /// Deliberately not registered.
+    impl Foo for Split {
+        fn foo(&mut self) -> i64 {
+            self.left
+        }
+    }
+
+    traitcast! {
+        /// Doc comments are forwarded too.
+        #[cfg(all())]
+        registered struct Split: Left, Right
+    }
+
+    traitcast!(registered struct A: Foo, Bar, crate::CastClone);
+    traitcast!(registered struct B: Foo, traits::Baz);
+
+    macro_rules! named {
+        ($($type:ident),+) => {
+            $(
+                impl Named for $type {
+                    fn name(&self) -> &'static str {
+                        stringify!($type)
+                    }
+                }
+
+                impl Measured for $type {
+                    fn size(&self) -> usize {
+                        std::mem::size_of::<$type>()
+                    }
+                }
+            )+
+        };
+    }
+
+    named!(A, B, Split);
+
+    traitcast!(A => dyn Foo + Send + Sync);
+    traitcast!(A => dyn Bar + Send);
+
+    /// Registered once per instantiation.
+    pub struct Wrapper<T>(pub T);
+
+    impl<T: Into<i64> + Copy + 'static> Foo for Wrapper<T> {
+        fn foo(&mut self) -> i64 {
+            self.0.into()
+        }
+    }
+
+    traitcast!(impl Foo; for Wrapper<u8>, Wrapper<i32>);
+
+    /// Registered with the deprecated aliases.
+    impl Named for Wrapper<u8> {
+        fn name(&self) -> &'static str {
+            "Wrapper<u8>"
+        }
+    }
+
+    crate::register_trait!(Named, Named_Traitcast);
+    crate::register_impl!(Named, Wrapper<u8>);
+
+    traitcast!(impl Named; for A, B);
+    traitcast!(impl Measured; for A, B);
+    traitcast!(impl Named, Measured; for Split);
 }
 
 use structs::*;
 use traits::*;
 
-use crate::Traitcast;
+use crate::{
+    impl_entry, Registry, Traitcast, TraitcastIterExt, TraitcastIterMutExt,
+};
+
+/// Makes a registry which only knows how to cast `T` into `Qux`.
+fn qux_registry(entry: traitcast_core::ImplEntry<dyn Qux>) -> Registry {
+    let mut registry = Registry::new();
+    registry.insert::<dyn Qux>(vec![entry].into_iter().collect());
+    registry
+}
 
 #[test]
 fn test_traitcast() {
-    let mut x: Box<Any> = Box::new(A { x: 0 });
-    let mut y: Box<Any> = Box::new(B { y: 1 });
+    let mut x: Box<dyn Any> = Box::new(A { x: 0 });
+    let mut y: Box<dyn Any> = Box::new(B { y: 1 });
 
     {
         // Can cast from Any to Bar
@@ -119,3 +322,1263 @@ fn test_traitcast() {
         assert_eq!(y.baz(), 8);
     }
 }
+
+#[test]
+fn test_push_pop_registry() {
+    let a = A { x: 1 };
+    let b = B { y: 2 };
+
+    crate::push_registry(qux_registry(impl_entry!(dyn Qux, A)));
+    crate::push_registry(qux_registry(impl_entry!(dyn Qux, B)));
+
+    // The most recently pushed registry wins
+    assert!(crate::cast_ref::<B, dyn Qux>(&b).is_some());
+    assert!(crate::cast_ref::<A, dyn Qux>(&a).is_none());
+
+    // Traits missing from the local registries fall back to the global one
+    assert!(crate::cast_ref::<A, dyn Bar>(&a).is_some());
+
+    let popped = crate::pop_registry().unwrap();
+    assert!(popped
+        .cast_into::<dyn Qux>()
+        .unwrap()
+        .from_ref(&b)
+        .is_some());
+
+    // Popping reveals the registry pushed first
+    let x: &dyn Qux = crate::cast_ref(&a).unwrap();
+    assert_eq!(x.qux(), -1);
+    assert!(crate::cast_ref::<B, dyn Qux>(&b).is_none());
+
+    assert!(crate::pop_registry().is_some());
+
+    // Popping an empty stack does nothing
+    assert!(crate::pop_registry().is_none());
+}
+
+#[test]
+fn test_enum_dispatch() {
+    let x: Box<dyn Any> = Box::new(Dispatch::A(A { x: 5 }));
+
+    // The enum casts into the trait it dispatches
+    let y: &dyn Bar = (*x).cast_ref().unwrap();
+    assert_eq!(y.bar(), 5);
+
+    // But not into traits only implemented by its variants
+    assert!(crate::cast_ref::<dyn Any, dyn Foo>(&*x).is_none());
+
+    // And it can be cast back to the enum itself
+    let y: Box<Dispatch> = x.cast_box().unwrap();
+    assert!(match *y {
+        Dispatch::A(ref a) => a.x == 5,
+    });
+}
+
+#[test]
+fn test_cast_filter() {
+    let items: Vec<Box<dyn Any>> = vec![
+        Box::new(A { x: 1 }),
+        Box::new(B { y: 2 }),
+        Box::new(7_i32),
+        Box::new(A { x: 3 }),
+    ];
+
+    // Only the two `A`s implement Bar
+    let bars: Vec<i64> = items
+        .iter()
+        .map(|x| &**x)
+        .cast_filter::<dyn Bar>()
+        .map(|x| x.bar())
+        .collect();
+    assert_eq!(bars, vec![1, 3]);
+
+    let big_bars: Vec<i64> = items
+        .iter()
+        .map(|x| &**x)
+        .cast_filter_map::<dyn Bar, _, _>(|x| Some(x.bar()).filter(|&x| x > 1))
+        .collect();
+    assert_eq!(big_bars, vec![3]);
+}
+
+#[test]
+fn test_cfg_impl() {
+    let a = A { x: 0 };
+    let b = B { y: 0 };
+
+    // The predicate holds, so only the first impl is registered
+    assert!(crate::implements_trait::<A, dyn CfgAll>(&a));
+    assert!(!crate::implements_trait::<B, dyn CfgAll>(&b));
+
+    // The predicate fails, so only the `else` impl is registered
+    assert!(!crate::implements_trait::<A, dyn CfgAny>(&a));
+    assert!(crate::implements_trait::<B, dyn CfgAny>(&b));
+}
+
+#[test]
+fn test_cfg_attr() {
+    use crate::TraitcastRegistered;
+
+    // Only the registration whose `cfg` holds is compiled in
+    assert!(crate::implements_trait::<A, dyn CfgAttr>(&A { x: 0 }));
+    assert!(!crate::implements_trait::<B, dyn CfgAttr>(&B { y: 0 }));
+
+    // The struct form forwards the attributes to the `TraitcastRegistered`
+    // impl, too
+    assert_eq!(Split::TRAITS, &["dyn Left", "dyn Right"]);
+}
+
+#[test]
+fn test_cast_arc() {
+    use crate::Traitcast;
+    use std::sync::Arc;
+
+    let x: Arc<dyn SendFoo> = Arc::new(A { x: 3 });
+    let x: Arc<dyn Bar> = crate::cast_arc(x).ok().unwrap();
+    assert_eq!(x.bar(), 3);
+
+    let y = Arc::new(B { y: 4 });
+    let keep = y.clone();
+    assert_eq!(Arc::strong_count(&keep), 2);
+
+    // Failure hands back the original pointer without touching the count
+    let y: Arc<dyn SendFoo> = y;
+    let y: Arc<dyn Any + Send + Sync> =
+        Traitcast::<dyn Bar>::cast_arc(y).err().unwrap();
+    assert_eq!(Arc::strong_count(&keep), 2);
+    let y: Arc<B> = y.downcast().unwrap();
+    assert!(Arc::ptr_eq(&y, &keep));
+    drop(y);
+    assert_eq!(Arc::strong_count(&keep), 1);
+}
+
+#[test]
+fn test_cast_rc() {
+    use crate::Traitcast;
+    use std::rc::Rc;
+
+    let x: Rc<dyn Foo> = Rc::new(A { x: 3 });
+    let x: Rc<dyn Bar> = crate::cast_rc(x).ok().unwrap();
+    assert_eq!(x.bar(), 3);
+
+    let y = Rc::new(B { y: 4 });
+    let keep = y.clone();
+
+    // Failure hands back the original pointer without touching the count
+    let y: Rc<dyn Foo> = y;
+    let y: Rc<dyn Any> = Traitcast::<dyn Bar>::cast_rc(y).err().unwrap();
+    assert_eq!(Rc::strong_count(&keep), 2);
+    let y: Rc<B> = y.downcast().unwrap();
+    assert!(Rc::ptr_eq(&y, &keep));
+}
+
+#[test]
+fn test_cast_slice_ref() {
+    let items: Vec<Box<dyn Any>> = vec![
+        Box::new(B { y: 1 }),
+        Box::new(A { x: 2 }),
+        Box::new("not castable"),
+    ];
+
+    let bars = crate::cast_slice_ref::<dyn Bar>(&items);
+    assert_eq!(bars.len(), 3);
+    assert!(bars[0].is_none());
+    assert_eq!(bars[1].unwrap().bar(), 2);
+    assert!(bars[2].is_none());
+
+    assert!(crate::cast_slice_ref::<dyn Bar>(&[]).is_empty());
+}
+
+#[test]
+fn test_cast_weak() {
+    use std::sync::{Arc, Weak};
+
+    let x: Arc<dyn SendFoo> = Arc::new(A { x: 3 });
+    let weak: Weak<dyn SendFoo> = Arc::downgrade(&x);
+    let bar: Arc<dyn Bar> = crate::cast_weak(&weak).unwrap();
+    assert_eq!(bar.bar(), 3);
+    assert_eq!(Arc::strong_count(&x), 2);
+    drop(bar);
+
+    // The upgrade succeeds but the cast fails
+    let y: Arc<dyn SendFoo> = Arc::new(B { y: 4 });
+    let weak = Arc::downgrade(&y);
+    assert!(crate::cast_weak::<_, dyn Bar>(&weak).is_none());
+    assert_eq!(Arc::strong_count(&y), 1);
+
+    // The upgrade fails
+    drop(y);
+    assert!(crate::cast_weak::<_, dyn Foo>(&weak).is_none());
+}
+
+#[test]
+fn test_cast_pin_box() {
+    use std::pin::Pin;
+
+    let x: Pin<Box<dyn Foo>> = Box::pin(Pinned {
+        x: 7,
+        _pin: std::marker::PhantomPinned,
+    });
+    let addr = &*x as *const dyn Foo as *const ();
+    let x: Pin<Box<dyn Bar>> = crate::cast_pin_box(x).ok().unwrap();
+    assert_eq!(x.bar(), 7);
+
+    // The pointee has not moved
+    assert_eq!(&*x as *const dyn Bar as *const (), addr);
+
+    let x: Pin<Box<dyn Any>> =
+        crate::cast_pin_box::<_, dyn traits::Baz>(x).err().unwrap();
+    assert!(x.is::<Pinned>());
+}
+
+#[test]
+fn test_registration_hook() {
+    use std::sync::Mutex;
+    use traitcast_core::inventory;
+
+    static SEEN: Mutex<Vec<(&str, &str)>> = Mutex::new(Vec::new());
+
+    // Vetoes everything castable into `Bar`
+    let registry = inventory::build_registry_with(|from_name, into_name| {
+        SEEN.lock().unwrap().push((from_name, into_name));
+        from_name != "dyn Bar"
+    });
+
+    let seen = SEEN.lock().unwrap();
+    for entry in &[("dyn Foo", "A"), ("dyn Bar", "A"), ("dyn Foo", "B")] {
+        assert!(seen.contains(entry));
+    }
+
+    let a = A { x: 1 };
+    let foo = registry.cast_into::<dyn Foo>().unwrap();
+    assert!(foo.from_ref(&a).is_some());
+    let bar = registry.cast_into::<dyn Bar>().unwrap();
+    assert!(bar.from_ref(&a).is_none());
+}
+
+#[test]
+fn test_try_cast() {
+    use crate::{try_cast_box, try_cast_mut, try_cast_ref, CastError};
+
+    let mut a = A { x: 1 };
+    let b = B { y: 2 };
+    assert_eq!(try_cast_ref::<_, dyn Bar>(&a).unwrap().unwrap().bar(), 1);
+    assert_eq!(
+        try_cast_mut::<_, dyn Foo>(&mut a).unwrap().unwrap().foo(),
+        2
+    );
+
+    // The trait is registered, but not for `B`
+    assert!(try_cast_ref::<_, dyn Bar>(&b).unwrap().is_none());
+    let b: Box<dyn Any> = Box::new(b);
+    assert!(try_cast_box::<_, dyn Bar>(b).unwrap().is_err());
+
+    // `Qux` is never registered globally
+    match try_cast_ref::<_, dyn Qux>(&a) {
+        Err(CastError::TraitNotRegistered { into_name }) => {
+            assert_eq!(into_name, "dyn traitcast::tests::traits::Qux")
+        }
+        _ => panic!("expected TraitNotRegistered"),
+    }
+    assert!(try_cast_mut::<_, dyn Qux>(&mut a).is_err());
+    assert!(try_cast_box::<_, dyn Qux>(Box::new(a)).is_err());
+}
+
+#[test]
+fn test_cast_clone_box() {
+    let x: Box<dyn Foo> = Box::new(A { x: 4 });
+    let y: Box<dyn Bar> = crate::cast_clone_box(&*x).unwrap();
+    assert_eq!(y.bar(), 4);
+
+    // The original is untouched
+    let mut x = x;
+    assert_eq!(x.foo(), 5);
+    assert_eq!(y.bar(), 4);
+
+    // `B` is not registered as `CastClone`
+    let b: Box<dyn Foo> = Box::new(B { y: 1 });
+    assert!(crate::cast_clone_box::<_, dyn Foo>(&*b).is_none());
+    // `A` is cloneable, but does not implement `Baz`
+    assert!(crate::cast_clone_box::<_, dyn traits::Baz>(&*x).is_none());
+}
+
+#[test]
+fn test_struct_foreign_type() {
+    use std::fmt::Display;
+
+    let x: Box<dyn Any> = Box::new(String::from("hello"));
+    let display: &dyn Display = crate::cast_ref(&*x).unwrap();
+    assert_eq!(display.to_string(), "hello");
+    let x: Box<String> = crate::cast_box(x).unwrap();
+    assert_eq!(*x, "hello");
+}
+
+#[test]
+fn test_registered_traits_const() {
+    use crate::TraitcastRegistered;
+
+    assert_eq!(
+        <A as TraitcastRegistered>::TRAITS,
+        &["dyn Foo", "dyn Bar", "dyn crate::CastClone"]
+    );
+
+    // Usable in const contexts
+    const B_TRAITS: usize = <B as TraitcastRegistered>::TRAITS.len();
+    assert_eq!(B_TRAITS, 2);
+}
+
+#[test]
+fn test_cast_guard() {
+    use crate::{cast_guard_mut, cast_guard_ref};
+    use std::sync::{Mutex, RwLock};
+
+    let lock: RwLock<Box<dyn Foo>> = RwLock::new(Box::new(A { x: 1 }));
+    {
+        let guard = lock.read().unwrap();
+        let bar: &dyn Bar = cast_guard_ref(&guard).unwrap();
+        assert_eq!(bar.bar(), 1);
+        assert!(cast_guard_ref::<_, _, dyn traits::Baz>(&guard).is_none());
+    }
+    {
+        let mut guard = lock.write().unwrap();
+        let foo: &mut dyn Foo = cast_guard_mut(&mut guard).unwrap();
+        assert_eq!(foo.foo(), 2);
+    }
+    let guard = lock.read().unwrap();
+    let bar: &dyn Bar = cast_guard_ref(&guard).unwrap();
+    assert_eq!(bar.bar(), 2);
+
+    // Works with any guard, not just those of `RwLock`
+    let mutex: Mutex<Box<dyn Foo>> = Mutex::new(Box::new(A { x: 3 }));
+    let guard = mutex.lock().unwrap();
+    let bar: &dyn Bar = cast_guard_ref(&guard).unwrap();
+    assert_eq!(bar.bar(), 3);
+}
+
+#[test]
+fn test_cast_indexed_refs() {
+    let items: Vec<Box<dyn Any>> = vec![
+        Box::new(A { x: 1 }),
+        Box::new(7_u8),
+        Box::new(B { y: 2 }),
+        Box::new(A { x: 3 }),
+    ];
+
+    let foos = crate::cast_indexed_refs::<dyn Foo>(&items);
+    let indices: Vec<usize> = foos.iter().map(|(i, _)| *i).collect();
+    assert_eq!(indices, vec![0, 2, 3]);
+
+    let bars: Vec<(usize, i64)> = crate::cast_indexed_refs::<dyn Bar>(&items)
+        .into_iter()
+        .map(|(i, x)| (i, x.bar()))
+        .collect();
+    assert_eq!(bars, vec![(0, 1), (3, 3)]);
+}
+
+#[test]
+fn test_can_cast_types() {
+    use std::any::TypeId;
+
+    assert!(crate::is_trait_registered::<dyn Foo>());
+    assert!(!crate::is_trait_registered::<dyn Qux>());
+    assert!(crate::can_cast_types(
+        TypeId::of::<B>(),
+        TypeId::of::<dyn traits::Baz>()
+    ));
+    assert!(!crate::can_cast_types(
+        TypeId::of::<A>(),
+        TypeId::of::<dyn traits::Baz>()
+    ));
+    assert!(!crate::can_cast_types(
+        TypeId::of::<A>(),
+        TypeId::of::<dyn Qux>()
+    ));
+
+    // Local registries are consulted first
+    crate::push_registry(qux_registry(impl_entry!(dyn Qux, A)));
+    assert!(crate::is_trait_registered::<dyn Qux>());
+    assert!(crate::can_cast_types(
+        TypeId::of::<A>(),
+        TypeId::of::<dyn Qux>()
+    ));
+    assert!(crate::can_cast_types(
+        TypeId::of::<A>(),
+        TypeId::of::<dyn Foo>()
+    ));
+    crate::pop_registry();
+}
+
+#[test]
+fn test_cast_box_vec() {
+    let xs: Vec<Box<dyn Foo>> = vec![
+        Box::new(A { x: 1 }),
+        Box::new(B { y: 2 }),
+        Box::new(A { x: 3 }),
+        Box::new(B { y: 4 }),
+    ];
+    let addrs: Vec<*const ()> = xs
+        .iter()
+        .map(|x| &**x as *const dyn Foo as *const ())
+        .collect();
+
+    let (bars, rest) = crate::cast_box_vec::<_, dyn Bar>(xs);
+    let values: Vec<i64> = bars.iter().map(|x| x.bar()).collect();
+    assert_eq!(values, vec![1, 3]);
+    let rest: Vec<i64> = rest
+        .into_iter()
+        .map(|x| x.downcast::<B>().ok().unwrap().y)
+        .collect();
+    assert_eq!(rest, vec![2, 4]);
+
+    // The successful boxes were not reallocated
+    assert_eq!(&*bars[0] as *const dyn Bar as *const (), addrs[0]);
+    assert_eq!(&*bars[1] as *const dyn Bar as *const (), addrs[2]);
+}
+
+#[test]
+fn test_global_introspection() {
+    let traits = crate::global_traits();
+    for name in &[
+        "dyn traitcast::tests::traits::Foo",
+        "dyn traitcast::tests::traits::Bar",
+        "dyn traitcast::tests::traits::Baz",
+    ] {
+        assert!(traits.contains(name), "missing {}", name);
+    }
+    assert!(!traits.contains(&"dyn traitcast::tests::traits::Qux"));
+
+    // At least A: Foo, Bar; B: Foo, Baz; and both structs themselves
+    assert!(crate::global_impl_count() >= 6);
+}
+
+#[test]
+fn test_global_registry() {
+    let registry = crate::global_registry();
+    assert!(registry.is_trait_registered::<dyn Baz>());
+    assert!(!registry.is_trait_registered::<dyn Qux>());
+
+    let mut bars = registry.impls_for::<dyn Bar>().unwrap();
+    bars.sort_unstable();
+    assert_eq!(bars, vec!["A", "Dispatch", "Pinned"]);
+
+    // Local registries do not show up in it
+    let mut local = Registry::new();
+    local
+        .insert::<dyn Qux>(vec![impl_entry!(dyn Qux, A)].into_iter().collect());
+    crate::push_registry(local);
+    assert!(!crate::global_registry().is_trait_registered::<dyn Qux>());
+    crate::pop_registry();
+
+    // Without `global_mutable` the registry can be kept around
+    #[cfg(not(feature = "global_mutable"))]
+    {
+        let registry: &'static Registry = crate::global_registry();
+        assert!(registry.is_trait_registered::<dyn Bar>());
+    }
+}
+
+#[test]
+fn test_cast_filter_mut() {
+    let mut items: Vec<Box<dyn Foo>> = vec![
+        Box::new(A { x: 1 }),
+        Box::new(B { y: 2 }),
+        Box::new(A { x: 3 }),
+    ];
+
+    // Every item implements `Foo`, and the mutations stick
+    let foos: Vec<i64> = items
+        .iter_mut()
+        .map(|x| &mut **x)
+        .cast_filter_mut::<dyn Foo>()
+        .map(|x| x.foo())
+        .collect();
+    assert_eq!(foos, vec![2, 4, 4]);
+
+    let bars: Vec<i64> = items
+        .iter()
+        .map(|x| &**x)
+        .cast_filter::<dyn Bar>()
+        .map(|x| x.bar())
+        .collect();
+    assert_eq!(bars, vec![2, 4]);
+}
+
+#[test]
+fn test_cast_filter_local() {
+    let items: Vec<Box<dyn Any>> =
+        vec![Box::new(A { x: 1 }), Box::new(B { y: 2 })];
+
+    // A pushed registry is honoured by the adaptors too
+    crate::push_registry(qux_registry(impl_entry!(dyn Qux, B)));
+    let quxes: Vec<i64> = items
+        .iter()
+        .map(|x| &**x)
+        .cast_filter::<dyn Qux>()
+        .map(|x| x.qux())
+        .collect();
+    crate::pop_registry();
+    assert_eq!(quxes, vec![-2]);
+}
+
+#[test]
+fn test_cast_mut_in_turn() {
+    // Each trait object borrows the whole value, so they are used one at a
+    // time rather than side by side
+    let mut split = Split { left: 1, right: 10 };
+    let left: &mut dyn Left = crate::cast_mut(&mut split).unwrap();
+    assert_eq!(left.bump_left(), 2);
+    let right: &mut dyn Right = crate::cast_mut(&mut split).unwrap();
+    assert_eq!(right.bump_right(), 11);
+    assert_eq!((split.left, split.right), (2, 11));
+}
+
+#[test]
+fn test_cast_mut_then_ref() {
+    // The mutable cast has to end before the shared one starts
+    let mut split = Split { left: 1, right: 10 };
+    let left: &mut dyn Left = crate::cast_mut(&mut split).unwrap();
+    assert_eq!(left.bump_left(), 2);
+    let named: &dyn Named = crate::cast_ref(&split).unwrap();
+    assert_eq!(named.name(), "Split");
+    assert_eq!(split.left, 2);
+}
+
+#[test]
+fn test_cast_ref_cache() {
+    let a = A { x: 1 };
+    let b = B { y: 2 };
+    let pinned = Pinned {
+        x: 3,
+        _pin: std::marker::PhantomPinned,
+    };
+
+    // Alternating concrete types miss the cache every time
+    for _ in 0..3 {
+        let bars: Vec<_> = [&a as &dyn Any, &b, &pinned, &a, &a, &7]
+            .iter()
+            .map(|x| crate::cast_ref::<dyn Any, dyn Bar>(*x).map(|x| x.bar()))
+            .collect();
+        assert_eq!(bars, vec![Some(1), None, Some(3), Some(1), Some(1), None]);
+    }
+
+    // Alternating target traits for the same concrete type, too
+    for _ in 0..3 {
+        let x: &dyn Any = &a;
+        assert_eq!(crate::cast_ref::<_, dyn Bar>(x).unwrap().bar(), 1);
+        assert!(crate::cast_ref::<_, dyn CfgAll>(x).is_some());
+        assert!(crate::cast_ref::<_, dyn CfgAny>(x).is_none());
+    }
+
+    // Pushing a registry invalidates the cache, so it takes effect at once
+    let x: &dyn Any = &a;
+    assert!(crate::cast_ref::<_, dyn Bar>(x).is_some());
+    let mut registry = Registry::new();
+    registry.insert::<dyn Bar>(
+        vec![impl_entry!(dyn Bar, Pinned)].into_iter().collect(),
+    );
+    crate::push_registry(registry);
+    assert!(crate::cast_ref::<_, dyn Bar>(x).is_none());
+    assert!(crate::cast_ref::<_, dyn Bar>(&pinned).is_some());
+
+    // Popping it does, too
+    crate::pop_registry();
+    assert!(crate::cast_ref::<_, dyn Bar>(&pinned).is_some());
+    assert!(crate::cast_ref::<_, dyn Bar>(x).is_some());
+}
+
+#[cfg(feature = "global_mutable")]
+#[test]
+fn test_register_global_impl() {
+    /// Only ever registered at runtime, by this test.
+    trait Runtime: crate::TraitcastFrom {
+        fn runtime(&self) -> i64;
+    }
+
+    impl Runtime for A {
+        fn runtime(&self) -> i64 {
+            self.x * 10
+        }
+    }
+
+    let a = A { x: 4 };
+    assert!(!crate::is_trait_registered::<dyn Runtime>());
+
+    crate::register_global_impl(impl_entry!(dyn Runtime, A));
+    assert_eq!(crate::cast_ref::<_, dyn Runtime>(&a).unwrap().runtime(), 40);
+    assert!(crate::global_registry().is_trait_registered::<dyn Runtime>());
+
+    // Refreshing rescans `inventory`, and keeps the runtime registration
+    crate::refresh_global_registry();
+    assert!(crate::implements_trait::<_, dyn Bar>(&a));
+    assert_eq!(crate::cast_ref::<_, dyn Runtime>(&a).unwrap().runtime(), 40);
+
+    // The iterator adaptors see it too
+    let items: Vec<&dyn Any> = vec![&a, &7];
+    let found: Vec<i64> = items
+        .into_iter()
+        .cast_filter::<dyn Runtime>()
+        .map(|x| x.runtime())
+        .collect();
+    assert_eq!(found, vec![40]);
+}
+
+#[test]
+fn test_cast_service() {
+    /// A minimal service container, keyed by concrete type.
+    struct Container {
+        services: anymap::AnyMap,
+    }
+
+    impl Container {
+        fn get<Service: 'static>(&self) -> Option<&Service> {
+            self.services.get::<Service>()
+        }
+    }
+
+    let mut services = anymap::AnyMap::new();
+    services.insert(A { x: 5 });
+    services.insert(B { y: 6 });
+    services.insert(7u8);
+    let container = Container { services };
+
+    // Each service is cast into the capabilities its type registered
+    let a = container.get::<A>().unwrap();
+    assert_eq!(crate::cast_service::<_, dyn Bar>(a).unwrap().bar(), 5);
+    let b = container.get::<B>().unwrap();
+    assert!(crate::cast_service::<_, dyn Bar>(b).is_none());
+    let byte = container.get::<u8>().unwrap();
+    assert!(crate::cast_service::<_, dyn Bar>(byte).is_none());
+
+    // Unregistered capabilities are `None` rather than a panic
+    assert!(crate::cast_service::<_, dyn Qux>(a).is_none());
+}
+
+#[test]
+fn test_with_local_registry() {
+    use std::panic::AssertUnwindSafe;
+
+    let a = A { x: 1 };
+
+    // The local registry only knows about `Qux`, and replaces the global
+    // table for nothing else
+    let mut registry = Registry::new();
+    registry
+        .insert::<dyn Qux>(vec![impl_entry!(dyn Qux, A)].into_iter().collect());
+    let qux = crate::with_local_registry(registry, || {
+        assert!(crate::implements_trait::<_, dyn Bar>(&a));
+        crate::cast_ref::<_, dyn Qux>(&a).map(|x| x.qux())
+    });
+    assert_eq!(qux, Some(-1));
+    assert!(!crate::is_trait_registered::<dyn Qux>());
+
+    // A conflicting table for `Bar` hides the global impl for `A`
+    let mut registry = Registry::new();
+    registry.insert::<dyn Bar>(
+        vec![impl_entry!(dyn Bar, Pinned)].into_iter().collect(),
+    );
+    crate::with_local_registry(registry, || {
+        assert!(!crate::implements_trait::<_, dyn Bar>(&a));
+    });
+    assert!(crate::implements_trait::<_, dyn Bar>(&a));
+
+    // Panics restore the stack, including anything left pushed
+    let mut registry = Registry::new();
+    registry
+        .insert::<dyn Qux>(vec![impl_entry!(dyn Qux, A)].into_iter().collect());
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        crate::with_local_registry(registry, || {
+            crate::push_registry(Registry::new());
+            panic!("test failed");
+        })
+    }));
+    assert!(result.is_err());
+    assert!(!crate::is_trait_registered::<dyn Qux>());
+    assert!(crate::pop_registry().is_none());
+}
+
+#[test]
+fn test_box_to_any() {
+    let mut store: Vec<Box<dyn Any>> = vec![Box::new(A { x: 1 })];
+
+    // Take an item out, process it as a `Foo`, and erase it again
+    let x = store.pop().unwrap();
+    let mut foo: Box<dyn Foo> = crate::cast_box(x).ok().unwrap();
+    assert_eq!(foo.foo(), 2);
+    store.push(crate::box_to_any(foo));
+
+    // It is still an `A`, so it can be cast into its other traits, too
+    let x = store.pop().unwrap();
+    assert!(x.is::<A>());
+    let bar: Box<dyn Bar> = crate::cast_box(x).ok().unwrap();
+    assert_eq!(bar.bar(), 2);
+}
+
+#[test]
+fn test_cast_boxed() {
+    let mut store: Vec<Box<dyn Any>> = vec![Box::new(A { x: 1 })];
+
+    let bar: &dyn Bar = crate::cast_ref_boxed(&store[0]).unwrap();
+    assert_eq!(bar.bar(), 1);
+    assert!(crate::cast_ref_boxed::<dyn Baz>(&store[0]).is_none());
+
+    let foo: &mut dyn Foo = crate::cast_mut_boxed(&mut store[0]).unwrap();
+    assert_eq!(foo.foo(), 2);
+    assert!(crate::cast_mut_boxed::<dyn Baz>(&mut store[0]).is_none());
+
+    // Casting the box itself fails, because `Box<dyn Any>` implements nothing
+    assert!(crate::cast_ref::<Box<dyn Any>, dyn Bar>(&store[0]).is_none());
+    assert_eq!(store[0].downcast_ref::<A>().unwrap().x, 2);
+}
+
+#[test]
+fn test_traitcast_lists() {
+    let mut store: Vec<Box<dyn Any>> = vec![
+        Box::new(A { x: 1 }),
+        Box::new(B { y: 2 }),
+        Box::new(Split { left: 0, right: 0 }),
+    ];
+
+    let names: Vec<&str> = store
+        .iter()
+        .map(|x| crate::cast_ref_boxed::<dyn Named>(x).unwrap().name())
+        .collect();
+    assert_eq!(names, vec!["A", "B", "Split"]);
+
+    let sizes: Vec<usize> = store
+        .iter_mut()
+        .map(|x| crate::cast_mut_boxed::<dyn Measured>(x).unwrap().size())
+        .collect();
+    assert_eq!(sizes, vec![8, 8, 16]);
+}
+
+#[test]
+fn test_downcast_concrete() {
+    use crate::{CastExt, Traitcast};
+
+    let x: Box<dyn Any> = Box::new(B { y: 3 });
+    let mut foo: Box<dyn Foo> = x.cast_box().ok().unwrap();
+    assert_eq!(foo.foo(), 6);
+
+    // Through the box, not on it, which would downcast the box itself
+    assert!(foo.downcast_concrete_ref::<B>().is_none());
+    let b: &mut B = (*foo).downcast_concrete_mut().unwrap();
+    b.y += 1;
+    assert_eq!((*foo).downcast_concrete_ref::<B>().unwrap().y, 7);
+    assert!((*foo).downcast_concrete_ref::<A>().is_none());
+
+    let baz: Box<dyn Baz> = foo.cast_box().ok().unwrap();
+    let baz = baz.downcast_concrete_box::<A>().err().unwrap();
+    let b: Box<B> = baz.downcast_concrete_box().ok().unwrap();
+    assert_eq!(b.y, 7);
+}
+
+#[test]
+fn test_global_cast_ext() {
+    use crate::GlobalCastExt;
+
+    let mut bar: Box<dyn Bar> = Box::new(A { x: 3 });
+    assert_eq!((*bar).cast_as_mut::<dyn Foo>().unwrap().foo(), 4);
+    assert_eq!((*bar).cast::<dyn Bar>().unwrap().bar(), 4);
+    assert!((*bar).cast::<dyn traits::Baz>().is_none());
+
+    // On the box itself, which is not registered
+    assert!(bar.cast::<dyn Foo>().is_none());
+}
+
+#[cfg(feature = "typetag_compat")]
+mod typetag_compat {
+    use serde::{Deserialize, Deserializer, Serialize};
+
+    use crate::{traitcast, Traitcast};
+
+    #[::typetag::serde]
+    trait Shape: crate::TraitcastFrom {
+        fn area(&self) -> f64;
+    }
+
+    trait Perimeter: crate::TraitcastFrom {
+        fn perimeter(&self) -> f64;
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Square {
+        side: f64,
+    }
+
+    #[::typetag::serde]
+    impl Shape for Square {
+        fn area(&self) -> f64 {
+            self.side * self.side
+        }
+    }
+
+    impl Perimeter for Square {
+        fn perimeter(&self) -> f64 {
+            4.0 * self.side
+        }
+    }
+
+    /// Known to `typetag`, but has no `Perimeter` impl.
+    #[derive(Serialize, Deserialize)]
+    struct Dot;
+
+    #[::typetag::serde]
+    impl Shape for Dot {
+        fn area(&self) -> f64 {
+            0.0
+        }
+    }
+
+    traitcast!(struct Square: Shape, Perimeter);
+    traitcast!(struct Dot: Shape);
+
+    #[derive(Deserialize)]
+    struct Outline {
+        #[serde(deserialize_with = "perimeter")]
+        shape: Box<dyn Perimeter>,
+    }
+
+    fn perimeter<'de, D>(d: D) -> Result<Box<dyn Perimeter>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        crate::typetag::deserialize_into::<dyn Shape, _, _>(d)
+    }
+
+    #[test]
+    fn test_typetag_cast_box() {
+        let shape: Box<dyn Shape> =
+            serde_json::from_str(r#"{"Square":{"side":2.0}}"#).unwrap();
+        assert_eq!(shape.area(), 4.0);
+        let shape: Box<dyn Perimeter> = shape.cast_box().ok().unwrap();
+        assert_eq!(shape.perimeter(), 8.0);
+
+        let shape: Box<dyn Shape> =
+            serde_json::from_str(r#"{"Dot":null}"#).unwrap();
+        assert!(crate::cast_box::<dyn Shape, dyn Perimeter>(shape).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_into() {
+        let outline: Outline =
+            serde_json::from_str(r#"{"shape":{"Square":{"side":3.0}}}"#)
+                .unwrap();
+        assert_eq!(outline.shape.perimeter(), 12.0);
+
+        let err = serde_json::from_str::<Outline>(r#"{"shape":{"Dot":null}}"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("cannot be cast into"), "{}", err);
+    }
+}
+
+#[test]
+fn test_cast_into_any() {
+    use crate::Traitcast;
+
+    let mut x: Box<dyn Foo> = Box::new(A { x: 1 });
+
+    let any: &dyn Any = crate::cast_ref(&*x).unwrap();
+    assert!(any.is::<A>());
+    assert!(crate::implements_trait::<dyn Foo, dyn Any>(&*x));
+
+    let any: &mut dyn Any = (*x).cast_mut().unwrap();
+    any.downcast_mut::<A>().unwrap().x = 5;
+    assert_eq!(x.foo(), 6);
+
+    // The result can be cast again like any other `dyn Any`
+    let any: Box<dyn Any> = x.cast_box().ok().unwrap();
+    let bar: Box<dyn Bar> = any.cast_box().ok().unwrap();
+    assert_eq!(bar.bar(), 6);
+}
+
+#[test]
+fn test_cast_ref_either() {
+    use crate::{cast_ref_either, Either};
+
+    let a: Box<dyn Any> = Box::new(A { x: 1 });
+    let b: Box<dyn Any> = Box::new(B { y: 2 });
+    let split: Box<dyn Any> = Box::new(Split { left: 3, right: 4 });
+
+    // `A` implements both, so the first trait wins
+    match cast_ref_either::<_, dyn Bar, dyn Baz>(&*a) {
+        Some(Either::Left(x)) => assert_eq!(x.bar(), 1),
+        _ => panic!("expected Bar"),
+    }
+    assert!(matches!(
+        cast_ref_either::<_, dyn Baz, dyn Bar>(&*a),
+        Some(Either::Right(_))
+    ));
+    assert!(matches!(
+        cast_ref_either::<_, dyn Bar, dyn Baz>(&*b),
+        Some(Either::Right(_))
+    ));
+    assert!(cast_ref_either::<_, dyn Bar, dyn Baz>(&*split).is_none());
+}
+
+#[test]
+fn test_cast_or_identity() {
+    use crate::{
+        cast_box_or_identity, cast_mut_or_identity, cast_ref_or_identity,
+    };
+
+    // Unlike `A`, `Split` is not registered for `Foo`
+    fn same<T: ?Sized + crate::TraitcastFrom + 'static>(x: &T) -> bool {
+        cast_ref_or_identity::<T, T>(x).is_some()
+    }
+
+    let mut split = Split { left: 1, right: 2 };
+    let x: &mut dyn Foo = &mut split;
+    assert!(crate::cast_ref::<dyn Foo, dyn Foo>(x).is_none());
+    assert!(same::<dyn Foo>(x));
+    assert_eq!(cast_mut_or_identity::<_, dyn Foo>(x).unwrap().foo(), 1);
+
+    // Casts between different traits still need registering
+    assert!(cast_ref_or_identity::<_, dyn Bar>(x).is_none());
+    let a: &dyn Foo = &A { x: 3 };
+    let bar: &dyn Bar = cast_ref_or_identity(a).unwrap();
+    assert_eq!(bar.bar(), 3);
+
+    let x: Box<dyn Foo> = Box::new(Split { left: 4, right: 5 });
+    let mut x: Box<dyn Foo> = cast_box_or_identity(x).ok().unwrap();
+    assert_eq!(x.foo(), 4);
+}
+
+#[test]
+fn test_cast_box_into_own_trait() {
+    // Goes through the table, so it works for registered types
+    let x: Box<dyn Foo> = Box::new(A { x: 3 });
+    let mut x: Box<dyn Foo> = crate::cast_box(x).ok().unwrap();
+    assert_eq!(x.foo(), 4);
+
+    // ...and fails for others, handing the box back
+    let x: Box<dyn Foo> = Box::new(Split { left: 1, right: 2 });
+    let x = crate::cast_box::<_, dyn Foo>(x).err().unwrap();
+    assert_eq!(x.downcast::<Split>().ok().unwrap().left, 1);
+}
+
+#[test]
+fn test_cast_ref_static() {
+    let leaked: &'static A = Box::leak(Box::new(A { x: 9 }));
+
+    // Shorten the lifetime, as e.g. a container lookup would
+    fn lookup(x: &A) -> &dyn Any {
+        x
+    }
+
+    // Safety: the value is leaked and never mutated again
+    let bar: &'static dyn Bar =
+        unsafe { crate::cast_ref_static(lookup(leaked)) }.unwrap();
+    assert_eq!(bar.bar(), 9);
+    assert!(unsafe { crate::cast_ref_static::<_, dyn Baz>(leaked) }.is_none());
+}
+
+#[test]
+fn test_cast_box_send() {
+    let x: Box<dyn SendFoo> = Box::new(A { x: 1 });
+    let mut foo: Box<dyn Foo + Send + Sync> =
+        crate::cast_box_send(x).ok().unwrap();
+    let foo = std::thread::spawn(move || {
+        assert_eq!(foo.foo(), 2);
+        foo
+    })
+    .join()
+    .unwrap();
+    assert_eq!(crate::box_to_any(foo).downcast::<A>().unwrap().x, 2);
+
+    // The bounds are kept on failure, too
+    let x: Box<dyn SendFoo> = Box::new(A { x: 3 });
+    let x: Box<dyn Any + Send + Sync> =
+        crate::cast_box_send::<_, dyn Baz>(x).err().unwrap();
+    let a = std::thread::spawn(move || x.downcast::<A>().unwrap())
+        .join()
+        .unwrap();
+    assert_eq!(a.x, 3);
+
+    // A plain `dyn Foo` is a different target, with its own table
+    let b: Box<dyn SendFoo> = Box::new(A { x: 4 });
+    assert!(crate::cast_box_send::<_, dyn Foo>(b).is_ok());
+    let b: Box<B> = Box::new(B { y: 5 });
+    assert!(crate::cast_box_send::<_, dyn Foo + Send + Sync>(b).is_err());
+}
+
+#[test]
+fn test_matches_trait() {
+    use crate::matches_trait;
+
+    let a: Box<dyn Any> = Box::new(A { x: 1 });
+    let b: Box<dyn Any> = Box::new(B { y: 2 });
+
+    let kind = if matches_trait!(&*a, dyn Baz) {
+        "baz"
+    } else if matches_trait!(&*a, dyn Bar) {
+        "bar"
+    } else {
+        "neither"
+    };
+    assert_eq!(kind, "bar");
+
+    assert!(matches_trait!(&*b, dyn Foo) && !matches_trait!(&*b, dyn Bar));
+    let foo: &dyn Foo = crate::cast_ref(&*b).unwrap();
+    assert!(matches_trait!(foo, dyn Baz));
+}
+
+#[test]
+fn test_generic_instantiations() {
+    let registry = crate::global_registry();
+    let mut names = registry.impls_for::<dyn Foo>().unwrap();
+    names.retain(|name| name.starts_with("Wrapper"));
+    names.sort_unstable();
+    assert_eq!(names, vec!["Wrapper<i32>", "Wrapper<u8>"]);
+
+    let mut x: Box<dyn Any> = Box::new(Wrapper(7u8));
+    let foo: &mut dyn Foo = crate::cast_mut(&mut *x).unwrap();
+    assert_eq!(foo.foo(), 7);
+    let x: Box<dyn Any> = Box::new(Wrapper(-3i32));
+    let mut foo: Box<dyn Foo> = crate::cast_box(x).ok().unwrap();
+    assert_eq!(foo.foo(), -3);
+
+    // Each instantiation has its own entry
+    let x: &dyn Any = &Wrapper(1u16);
+    assert!(crate::cast_ref::<_, dyn Foo>(x).is_none());
+}
+
+#[test]
+fn test_load_inventory() {
+    use crate::{impl_entry, Registry};
+
+    // `Qux` is never registered with `traitcast!`
+    let mut registry = Registry::new();
+    registry.register_impl(impl_entry!(dyn Qux, A));
+    registry.load_inventory();
+
+    let a: Box<dyn Any> = Box::new(A { x: 2 });
+    let qux: &dyn Qux = registry.try_cast_ref(&*a).unwrap();
+    assert_eq!(qux.qux(), -2);
+    let bar: &dyn Bar = registry.try_cast_ref(&*a).unwrap();
+    assert_eq!(bar.bar(), 2);
+
+    let b: Box<dyn Any> = Box::new(B { y: 3 });
+    assert!(registry.try_cast_ref::<_, dyn Qux>(&*b).is_err());
+    let baz: Box<dyn Baz> = registry.try_cast_box(b).unwrap();
+    assert_eq!(baz.baz(), 3);
+}
+
+#[test]
+fn test_bounded_trait_object() {
+    let x: Box<dyn Any> = Box::new(A { x: 5 });
+
+    let bar: &(dyn Bar + Send) = crate::cast_ref(&*x).unwrap();
+    assert_eq!(bar.bar(), 5);
+    let bar: Box<dyn Bar + Send> = crate::cast_box(x).ok().unwrap();
+    let x = std::thread::spawn(move || bar.bar()).join().unwrap();
+    assert_eq!(x, 5);
+
+    // Only `A` is registered for the bounded type
+    let b: &dyn Any = &B { y: 1 };
+    assert!(crate::cast_ref::<_, dyn Bar + Send>(b).is_none());
+    let registry = crate::global_registry();
+    assert_eq!(registry.impls_for::<dyn Bar + Send>(), Some(vec!["A"]));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_cast_refs() {
+    let values: Vec<Box<dyn Any + Sync>> = (0..1000)
+        .map(|i| -> Box<dyn Any + Sync> {
+            if i % 2 == 0 {
+                Box::new(A { x: i })
+            } else {
+                Box::new(B { y: i })
+            }
+        })
+        .collect();
+    let items: Vec<&(dyn Any + Sync)> = values.iter().map(|x| &**x).collect();
+
+    // The target must be `Sync`, so use the bounded `Foo` table
+    type SyncFoo = dyn Foo + Send + Sync;
+    let addr =
+        |x: Option<&SyncFoo>| x.map(|x| x as *const SyncFoo as *const u8);
+
+    let par: Vec<_> = crate::par_cast_refs::<SyncFoo>(&items)
+        .into_iter()
+        .map(addr)
+        .collect();
+    let seq: Vec<_> = items
+        .iter()
+        .map(|&x| {
+            let x: &dyn Any = x;
+            addr(crate::cast_ref::<_, SyncFoo>(x))
+        })
+        .collect();
+    assert_eq!(par, seq);
+    assert_eq!(par.iter().flatten().count(), 500);
+}
+
+#[test]
+fn test_register_aliases() {
+    let x: &dyn Any = &Wrapper(3u8);
+    let named = crate::cast_ref::<_, dyn Named>(x).map(|x| x.name());
+    assert_eq!(named, Some("Wrapper<u8>"));
+
+    // The alias registers exactly the impls it names
+    let y: &dyn Any = &Wrapper(3i32);
+    assert!(crate::cast_ref::<_, dyn Named>(y).is_none());
+}
+
+/// Counts the allocations made by each thread, so that tests running in
+/// parallel don't see each other's.
+mod counting_alloc {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    pub struct Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|x| x.set(x.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    /// The number of allocations made by the current thread so far.
+    pub fn allocations() -> usize {
+        ALLOCATIONS.with(|x| x.get())
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: counting_alloc::Counting = counting_alloc::Counting;
+
+#[test]
+fn test_cast_box_does_not_allocate() {
+    use counting_alloc::allocations;
+
+    let addr = |x: &dyn Any| x as *const dyn Any as *const u8;
+
+    // Make sure the global registry is built before counting
+    let x: Box<dyn Any> = Box::new(A { x: 1 });
+    let x: Box<dyn Foo> = crate::cast_box(x).ok().unwrap();
+    let before = addr(x.as_any_ref());
+
+    let start = allocations();
+    let x: Box<dyn Bar> = crate::cast_box(x).ok().unwrap();
+    let x: Box<dyn Any> = crate::box_to_any(x);
+    let x = crate::cast_box::<_, dyn Baz>(x).err().unwrap();
+    let x: Box<dyn Foo> = crate::cast_box(x).ok().unwrap();
+    assert_eq!(allocations(), start);
+
+    // The box still owns the original allocation
+    assert_eq!(addr(x.as_any_ref()), before);
+
+    // Whereas the counter does see allocations
+    let _y = std::hint::black_box(Box::new(0u8));
+    assert_eq!(allocations(), start + 1);
+}
+
+#[test]
+fn test_implements_all_any() {
+    use std::any::TypeId;
+
+    let foo = TypeId::of::<dyn Foo>();
+    let bar = TypeId::of::<dyn Bar>();
+    let baz = TypeId::of::<dyn Baz>();
+    let qux = TypeId::of::<dyn Qux>();
+
+    let a: &dyn Foo = &A { x: 1 };
+    assert!(crate::implements_all(a, &[foo, bar]));
+    assert!(!crate::implements_all(a, &[foo, baz]));
+    assert!(crate::implements_any(a, &[baz, bar]));
+    assert!(!crate::implements_any(a, &[baz]));
+
+    // Unregistered traits are not implemented
+    assert!(!crate::implements_all(a, &[foo, qux]));
+    assert!(!crate::implements_any(a, &[qux]));
+
+    assert!(crate::implements_all(a, &[]));
+    assert!(!crate::implements_any(a, &[]));
+}
+
+#[test]
+fn test_cast_box_checked() {
+    let x: Box<dyn Foo> = Box::new(B { y: 3 });
+    let err = crate::cast_box_checked::<_, dyn Bar>(x).err().unwrap();
+    assert_eq!(err.from_name, std::any::type_name::<dyn Foo>());
+    assert_eq!(err.into_name, std::any::type_name::<dyn Bar>());
+    assert_eq!(
+        err.to_string(),
+        "cannot cast box of 'dyn traitcast::tests::traits::Foo' into \
+         'dyn traitcast::tests::traits::Bar'"
+    );
+
+    // The box survives the failed cast
+    let x = err.into_inner();
+    let mut x: Box<dyn Foo> = crate::cast_box_checked(x).unwrap();
+    assert_eq!(x.foo(), 6);
+
+    // Unregistered traits fail without panicking
+    let err = crate::cast_box_checked::<_, dyn Qux>(x).err().unwrap();
+    assert!(err.into_inner().is::<B>());
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_trace_global_cast_failure() {
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Records the fields of every event as `name=value` strings.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for Recorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            let mut events = self.0.lock().unwrap();
+            let event = events.last_mut().unwrap();
+            event.push_str(&format!("{}={:?} ", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event) {
+            self.0.lock().unwrap().push(String::new());
+            event.record(&mut self.clone());
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let b = B { y: 1 };
+        assert!(crate::cast_ref::<_, dyn Bar>(&b).is_none());
+        assert!(crate::cast_ref::<_, dyn Foo>(&b).is_some());
+    });
+
+    let events = recorder.0.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].contains("reason=\"impl not registered\""));
+    assert!(
+        events[0].contains(&format!("from={:?}", std::any::TypeId::of::<B>()))
+    );
+    assert!(
+        events[0].contains("into_name=\"dyn traitcast::tests::traits::Bar\"")
+    );
+}
@@ -0,0 +1,37 @@
+/*!
+This module bridges `traitcast` with the `typetag` crate, so that trait
+objects deserialized by `typetag` can be cast into other traits as part of
+deserialization. Requires the `typetag_compat` feature.
+
+A `Box<dyn Shape>` deserialized by `typetag` is an ordinary box, so if
+`Shape` extends `TraitcastFrom` it can already be cast with `cast_box`. As
+always, the cast only succeeds if the impl for the concrete type has been
+registered via `traitcast!`; `typetag`'s own registration is not enough.
+*/
+use serde::de::{Deserialize, Deserializer, Error};
+
+use crate::TraitcastFrom;
+
+/// Deserializes a `Box<From>`, typically a `typetag` trait object, and casts
+/// it into `Box<To>`. Fails with a custom error if the concrete type which
+/// was deserialized does not have a registered impl of `To`.
+///
+/// This fits `#[serde(deserialize_with = "...")]` on a field of type
+/// `Box<To>`, through a wrapper that fixes `From`.
+pub fn deserialize_into<'de, From, To, D>(
+    deserializer: D,
+) -> Result<Box<To>, D::Error>
+where
+    From: TraitcastFrom + ?Sized,
+    Box<From>: Deserialize<'de>,
+    To: ?Sized + 'static,
+    D: Deserializer<'de>,
+{
+    let x = Box::<From>::deserialize(deserializer)?;
+    crate::cast_box(x).map_err(|_| {
+        D::Error::custom(format_args!(
+            "deserialized value cannot be cast into {}",
+            std::any::type_name::<To>()
+        ))
+    })
+}
@@ -0,0 +1,38 @@
+//! The global registry is built once per process, so the hook is tested in
+//! its own test binary, before anything else can build it.
+
+use std::sync::Mutex;
+
+use traitcast::traitcast;
+
+trait Foo: traitcast::TraitcastFrom {}
+trait Bar: traitcast::TraitcastFrom {}
+
+struct A;
+impl Foo for A {}
+impl Bar for A {}
+
+traitcast!(impl Foo; for A);
+traitcast!(impl Bar; for A);
+
+static SEEN: Mutex<Vec<(&str, &str)>> = Mutex::new(Vec::new());
+
+#[test]
+fn test_global_registration_hook() {
+    // Vetoes everything castable into `Bar`
+    traitcast::set_registration_hook(|from_name, into_name| {
+        SEEN.lock().unwrap().push((from_name, into_name));
+        from_name != "dyn Bar"
+    })
+    .unwrap();
+
+    assert!(traitcast::cast_ref::<_, dyn Foo>(&A).is_some());
+    assert!(traitcast::try_cast_ref::<_, dyn Bar>(&A).unwrap().is_none());
+
+    let seen = SEEN.lock().unwrap();
+    assert!(seen.contains(&("dyn Foo", "A")));
+    assert!(seen.contains(&("dyn Bar", "A")));
+
+    // Too late once the global registry has been built
+    assert!(traitcast::set_registration_hook(|_, _| true).is_err());
+}